@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::MEMORY_64K;
+use crate::error::AsmError;
+use crate::instruction::Instruction;
+use crate::PROGRAM_START;
+
+/// How deep INCLUDE directives may nest before we assume they form a cycle.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Every instruction mnemonic the assembler knows, used to tell a typo from
+/// a known instruction with the wrong operands.
+const MNEMONICS: [&str; 33] = [
+    "HALT", "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SYS", "JP", "CALL",
+    "SE", "SNE", "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL",
+    "RND", "DRW", "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+/// Assembles mnemonic source into a ROM image meant to be loaded at
+/// [`PROGRAM_START`].
+///
+/// The syntax is the one [`crate::disassemble`] prints: Cowgod's mnemonics
+/// (`CALL sub`, `ADD V0, V1`, `LD [I], V3`, `RET`) with the SUPER-CHIP and
+/// XO-CHIP additions, one instruction per line and `;` starting a comment.
+/// On top of that it understands:
+///
+/// - labels, written `name:` at the start of a line;
+/// - constants, written `NAME EQU value`;
+/// - `DB` and `DW` for data bytes and big-endian words;
+/// - `ORG addr` to continue at a later address, padding with zeros;
+/// - `INCLUDE "file"` to splice in another file, relative to the including
+///   one.
+///
+/// Numbers are decimal, `0x` hex or `0b` binary, and anywhere a number is
+/// expected a sum such as `sprites + 5` or `END - START` may be used.
+/// Constants and `ORG` can only refer to symbols defined above them.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new();
+    assembler.read(source, None, 0)?;
+    assembler.emit()
+}
+
+/// Like [`assemble`] for a source file. Errors name the file, or the
+/// included file, they were found in.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AsmError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|err| AsmError {
+        path: Some(path.to_path_buf()),
+        line: 0,
+        message: format!("cannot read source: {}", err),
+    })?;
+    let mut assembler = Assembler::new();
+    assembler.read(&source, Some(path), 0)?;
+    assembler.emit()
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    path: Option<PathBuf>,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError {
+            path: self.path.clone(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+}
+
+enum Item {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<String>,
+    },
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+}
+
+struct Statement {
+    location: Location,
+    addr: usize,
+    item: Item,
+}
+
+enum Operand {
+    V(u8),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Long(i64),
+    Value(i64),
+}
+
+/// Two passes: [`Assembler::read`] lays out every statement and collects
+/// the symbols, then [`Assembler::emit`] encodes them now that every label
+/// is known.
+struct Assembler {
+    addr: usize,
+    symbols: HashMap<String, i64>,
+    statements: Vec<Statement>,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Assembler {
+            addr: PROGRAM_START,
+            symbols: HashMap::new(),
+            statements: Vec::new(),
+        }
+    }
+
+    fn read(&mut self, source: &str, path: Option<&Path>, depth: usize) -> Result<(), AsmError> {
+        for (i, line) in source.lines().enumerate() {
+            let location = Location {
+                path: path.map(Path::to_path_buf),
+                line: i + 1,
+            };
+            self.read_line(line, &location, depth)?;
+        }
+        Ok(())
+    }
+
+    fn read_line(&mut self, line: &str, location: &Location, depth: usize) -> Result<(), AsmError> {
+        let mut line = line.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = line.split_once(':') {
+            if is_identifier(label.trim()) {
+                self.define(label.trim(), self.addr as i64, location)?;
+                line = rest.trim();
+            }
+        }
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let (word, rest) = split_word(line);
+        let (next, value) = split_word(rest);
+        if next.eq_ignore_ascii_case("EQU") {
+            let value = self.eval(value, location)?;
+            return self.define(word, value, location);
+        }
+        match word.to_ascii_uppercase().as_str() {
+            "DB" => self.push(Item::Bytes(split_operands(rest)), location),
+            "DW" => self.push(Item::Words(split_operands(rest)), location),
+            "ORG" => {
+                let addr = self.eval(rest, location)?;
+                if addr < self.addr as i64 || addr > MEMORY_64K as i64 {
+                    return Err(location.error(format!(
+                        "ORG {:#x} is before the current address {:#x} or past the end of memory",
+                        addr, self.addr
+                    )));
+                }
+                self.addr = addr as usize;
+                Ok(())
+            }
+            "INCLUDE" => self.include(rest, location, depth),
+            _ => self.push(
+                Item::Instruction {
+                    mnemonic: word.to_ascii_uppercase(),
+                    operands: split_operands(rest),
+                },
+                location,
+            ),
+        }
+    }
+
+    fn include(&mut self, name: &str, location: &Location, depth: usize) -> Result<(), AsmError> {
+        let name = name
+            .strip_prefix('"')
+            .and_then(|name| name.strip_suffix('"'))
+            .ok_or_else(|| location.error("INCLUDE needs a file name in double quotes"))?;
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(location.error("includes are nested too deeply"));
+        }
+        let base = location
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new(""));
+        let path = base.join(name);
+        let source = fs::read_to_string(&path)
+            .map_err(|err| location.error(format!("cannot include {}: {}", path.display(), err)))?;
+        self.read(&source, Some(&path), depth + 1)
+    }
+
+    fn define(&mut self, name: &str, value: i64, location: &Location) -> Result<(), AsmError> {
+        if !is_identifier(name) {
+            return Err(location.error(format!("invalid symbol name '{}'", name)));
+        }
+        if is_reserved(name) {
+            return Err(location.error(format!("'{}' is a reserved word", name)));
+        }
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(location.error(format!("'{}' is already defined", name)));
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, item: Item, location: &Location) -> Result<(), AsmError> {
+        let size = match &item {
+            Item::Bytes(values) | Item::Words(values) if values.is_empty() => {
+                return Err(location.error("data directive needs at least one value"));
+            }
+            Item::Bytes(values) => values.len(),
+            Item::Words(values) => values.len() * 2,
+            Item::Instruction { mnemonic, operands } => {
+                let long = operands
+                    .get(1)
+                    .is_some_and(|operand| split_word(operand).0.eq_ignore_ascii_case("LONG"));
+                if mnemonic == "LD" && long {
+                    4
+                } else {
+                    2
+                }
+            }
+        };
+        if self.addr + size > MEMORY_64K {
+            return Err(location.error("program runs past the end of memory"));
+        }
+        self.statements.push(Statement {
+            location: location.clone(),
+            addr: self.addr,
+            item,
+        });
+        self.addr += size;
+        Ok(())
+    }
+
+    fn emit(&self) -> Result<Vec<u8>, AsmError> {
+        let mut rom = Vec::new();
+        for statement in &self.statements {
+            rom.resize(statement.addr - PROGRAM_START, 0);
+            let location = &statement.location;
+            match &statement.item {
+                Item::Bytes(values) => {
+                    for value in values {
+                        rom.push(byte(self.eval(value, location)?, location)?);
+                    }
+                }
+                Item::Words(values) => {
+                    for value in values {
+                        let word = bits(self.eval(value, location)?, 16, location)?;
+                        rom.extend(word.to_be_bytes());
+                    }
+                }
+                Item::Instruction { mnemonic, operands } => {
+                    let operands = operands
+                        .iter()
+                        .map(|operand| self.operand(operand, location))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let instruction = instruction(mnemonic, &operands, location)?;
+                    rom.extend(instruction.encode().to_be_bytes());
+                    if let [_, Operand::Long(addr)] = operands[..] {
+                        rom.extend(bits(addr, 16, location)?.to_be_bytes());
+                    }
+                }
+            }
+        }
+        Ok(rom)
+    }
+
+    fn operand(&self, text: &str, location: &Location) -> Result<Operand, AsmError> {
+        if let Some(x) = parse_register(text) {
+            return Ok(Operand::V(x));
+        }
+        let operand = match text.to_ascii_uppercase().as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "HF" => Operand::Hf,
+            "B" => Operand::B,
+            "R" => Operand::R,
+            _ => match split_word(text) {
+                (word, addr) if word.eq_ignore_ascii_case("LONG") => {
+                    Operand::Long(self.eval(addr, location)?)
+                }
+                _ => Operand::Value(self.eval(text, location)?),
+            },
+        };
+        Ok(operand)
+    }
+
+    /// Evaluates a sum of numbers and symbols such as `sprites + 5`.
+    fn eval(&self, expr: &str, location: &Location) -> Result<i64, AsmError> {
+        let mut rest = expr.trim();
+        let mut sign = 1;
+        if let Some(negated) = rest.strip_prefix('-') {
+            sign = -1;
+            rest = negated;
+        }
+        let mut total: i64 = 0;
+        loop {
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            total = self
+                .term(rest[..end].trim(), location)?
+                .checked_mul(sign)
+                .and_then(|term| total.checked_add(term))
+                .ok_or_else(|| location.error(format!("'{}' overflows", expr.trim())))?;
+            if end == rest.len() {
+                return Ok(total);
+            }
+            sign = if rest.as_bytes()[end] == b'+' { 1 } else { -1 };
+            rest = &rest[end + 1..];
+        }
+    }
+
+    fn term(&self, term: &str, location: &Location) -> Result<i64, AsmError> {
+        if term.is_empty() {
+            return Err(location.error("missing value"));
+        }
+        if term.starts_with(|c: char| c.is_ascii_digit()) {
+            return parse_number(term)
+                .and_then(|n| i64::try_from(n).ok())
+                .ok_or_else(|| location.error(format!("invalid number '{}'", term)));
+        }
+        self.symbols
+            .get(term)
+            .copied()
+            .ok_or_else(|| location.error(format!("undefined symbol '{}'", term)))
+    }
+}
+
+fn instruction(
+    mnemonic: &str,
+    operands: &[Operand],
+    location: &Location,
+) -> Result<Instruction, AsmError> {
+    use Operand::*;
+
+    let nibble = |n: i64| bits(n, 4, location).map(|n| n as u8);
+    let addr = |a: i64| bits(a, 12, location);
+    let byte = |nn: i64| byte(nn, location);
+    let instruction = match (mnemonic, operands) {
+        ("HALT", []) => Instruction::Halt,
+        ("CLS", []) => Instruction::ClearScreen,
+        ("RET", []) => Instruction::Return,
+        ("SCD", &[Value(n)]) => Instruction::ScrollDown { n: nibble(n)? },
+        ("SCU", &[Value(n)]) => Instruction::ScrollUp { n: nibble(n)? },
+        ("SCR", []) => Instruction::ScrollRight,
+        ("SCL", []) => Instruction::ScrollLeft,
+        ("EXIT", []) => Instruction::Exit,
+        ("LOW", []) => Instruction::LowRes,
+        ("HIGH", []) => Instruction::HighRes,
+        ("SYS", &[Value(a)]) => Instruction::Sys { nnn: addr(a)? },
+        ("JP", &[Value(a)]) => Instruction::Jump { nnn: addr(a)? },
+        ("JP", &[V(0), Value(a)]) => {
+            let nnn = addr(a)?;
+            Instruction::JumpOffset {
+                x: (nnn >> 8) as u8,
+                nnn,
+            }
+        }
+        ("CALL", &[Value(a)]) => Instruction::Call { nnn: addr(a)? },
+        ("SE", &[V(x), Value(nn)]) => Instruction::SkipEqImm { x, nn: byte(nn)? },
+        ("SE", &[V(x), V(y)]) => Instruction::SkipEqReg { x, y },
+        ("SNE", &[V(x), Value(nn)]) => Instruction::SkipNeImm { x, nn: byte(nn)? },
+        ("SNE", &[V(x), V(y)]) => Instruction::SkipNeReg { x, y },
+        ("SAVE", &[V(x), V(y)]) => Instruction::SaveRange { x, y },
+        ("LOAD", &[V(x), V(y)]) => Instruction::LoadRange { x, y },
+        ("LD", &[V(x), Value(nn)]) => Instruction::LoadImm { x, nn: byte(nn)? },
+        ("LD", &[V(x), V(y)]) => Instruction::Move { x, y },
+        ("LD", &[I, Value(a)]) => Instruction::LoadIndex { nnn: addr(a)? },
+        ("LD", &[I, Long(_)]) => Instruction::LongLoadIndex,
+        ("LD", &[V(x), Dt]) => Instruction::LoadDelay { x },
+        ("LD", &[V(x), K]) => Instruction::WaitKey { x },
+        ("LD", &[Dt, V(x)]) => Instruction::SetDelay { x },
+        ("LD", &[St, V(x)]) => Instruction::SetSound { x },
+        ("LD", &[F, V(x)]) => Instruction::LoadFont { x },
+        ("LD", &[Hf, V(x)]) => Instruction::LoadBigFont { x },
+        ("LD", &[B, V(x)]) => Instruction::StoreBcd { x },
+        ("LD", &[IndirectI, V(x)]) => Instruction::StoreRegisters { x },
+        ("LD", &[V(x), IndirectI]) => Instruction::LoadRegisters { x },
+        ("LD", &[R, V(x)]) => Instruction::SaveFlags { x },
+        ("LD", &[V(x), R]) => Instruction::LoadFlags { x },
+        ("ADD", &[V(x), Value(nn)]) => Instruction::AddImm { x, nn: byte(nn)? },
+        ("ADD", &[V(x), V(y)]) => Instruction::Add { x, y },
+        ("ADD", &[I, V(x)]) => Instruction::AddIndex { x },
+        ("OR", &[V(x), V(y)]) => Instruction::Or { x, y },
+        ("AND", &[V(x), V(y)]) => Instruction::And { x, y },
+        ("XOR", &[V(x), V(y)]) => Instruction::Xor { x, y },
+        ("SUB", &[V(x), V(y)]) => Instruction::Sub { x, y },
+        ("SUBN", &[V(x), V(y)]) => Instruction::SubN { x, y },
+        ("SHR", &[V(x)]) => Instruction::ShiftRight { x, y: x },
+        ("SHR", &[V(x), V(y)]) => Instruction::ShiftRight { x, y },
+        ("SHL", &[V(x)]) => Instruction::ShiftLeft { x, y: x },
+        ("SHL", &[V(x), V(y)]) => Instruction::ShiftLeft { x, y },
+        ("RND", &[V(x), Value(nn)]) => Instruction::Random { x, nn: byte(nn)? },
+        ("DRW", &[V(x), V(y), Value(n)]) => Instruction::Draw {
+            x,
+            y,
+            n: nibble(n)?,
+        },
+        ("SKP", &[V(x)]) => Instruction::SkipKeyPressed { x },
+        ("SKNP", &[V(x)]) => Instruction::SkipKeyNotPressed { x },
+        ("PLANE", &[Value(n)]) => Instruction::SelectPlanes { n: nibble(n)? },
+        ("AUDIO", []) => Instruction::LoadAudio,
+        ("PITCH", &[V(x)]) => Instruction::SetPitch { x },
+        _ if MNEMONICS.contains(&mnemonic) => {
+            return Err(location.error(format!("invalid operands for {}", mnemonic)));
+        }
+        _ => return Err(location.error(format!("unknown mnemonic '{}'", mnemonic))),
+    };
+    Ok(instruction)
+}
+
+/// Checks that `value` fits in an unsigned field `width` bits wide.
+fn bits(value: i64, width: u32, location: &Location) -> Result<u16, AsmError> {
+    if (0..1 << width).contains(&value) {
+        Ok(value as u16)
+    } else {
+        Err(location.error(format!("value {:#x} does not fit in {} bits", value, width)))
+    }
+}
+
+/// Checks that `value` fits in a byte, allowing negative numbers so that
+/// `ADD V0, -1` works.
+fn byte(value: i64, location: &Location) -> Result<u8, AsmError> {
+    if (-0x80..=0xFF).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(location.error(format!("value {:#x} does not fit in a byte", value)))
+    }
+}
+
+/// Parses a decimal, `0x` hex or `0b` binary number, the forms the
+/// assembler and the debugger accept.
+pub fn parse_number(s: &str) -> Option<u64> {
+    let lower = s.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        u64::from_str_radix(bin, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+/// Parses a register name, `V0`-`VF` in either case.
+pub fn parse_register(s: &str) -> Option<u8> {
+    let digit = s.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn is_reserved(s: &str) -> bool {
+    const WORDS: [&str; 9] = ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG"];
+    parse_register(s).is_some() || WORDS.iter().any(|word| word.eq_ignore_ascii_case(s))
+}
+
+/// Splits off the first whitespace-separated word.
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    match s.find(char::is_whitespace) {
+        Some(end) => (&s[..end], s[end..].trim_start()),
+        None => (s, ""),
+    }
+}
+
+fn split_operands(s: &str) -> Vec<String> {
+    if s.trim().is_empty() {
+        return Vec::new();
+    }
+    s.split(',')
+        .map(|operand| operand.trim().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::decode;
+
+    #[test]
+    fn disassembled_instructions_assemble_to_the_same_opcode() {
+        for opcode in 0..=u16::MAX {
+            let instruction = decode(opcode);
+            if matches!(
+                instruction,
+                Instruction::Illegal(_) | Instruction::LongLoadIndex
+            ) {
+                continue;
+            }
+            let rom = assemble(&instruction.to_string())
+                .unwrap_or_else(|err| panic!("{:04x} {}: {}", opcode, instruction, err));
+            assert_eq!(decode(u16::from_be_bytes([rom[0], rom[1]])), instruction);
+        }
+    }
+
+    #[test]
+    fn labels_can_be_used_before_they_are_defined() {
+        let rom = assemble("JP end\nCLS\nend: RET").unwrap();
+        assert_eq!(rom, [0x12, 0x04, 0x00, 0xE0, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn org_pads_with_zeros() {
+        let rom = assemble("CLS\nORG 0x206\nRET").unwrap();
+        assert_eq!(rom, [0x00, 0xE0, 0x00, 0x00, 0x00, 0x00, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn overflowing_expressions_are_errors() {
+        let err = assemble("CLS\nDB 0x7fffffffffffffff + 1").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn errors_report_the_line_number() {
+        let err = assemble("CLS\n\nLD V0, 0x100").unwrap_err();
+        assert_eq!((err.path, err.line), (None, 3));
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+
+use crate::asm::{parse_number, parse_register};
+use crate::cpu::CPU;
+
+/// Something a [`Condition`] or [`Watchpoint`] can look at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(u8),
+    Index,
+    /// The byte at this address.
+    Memory(usize),
+    DelayTimer,
+    SoundTimer,
+}
+
+impl Operand {
+    /// Parses `V0`-`VF`, `I`, `DT`, `ST` or a bracketed address like `[0x300]`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(x) = parse_register(s) {
+            return Some(Operand::Register(x));
+        }
+        if let Some(addr) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            return parse_number(addr.trim())
+                .and_then(|addr| usize::try_from(addr).ok())
+                .map(Operand::Memory);
+        }
+        match s.to_ascii_uppercase().as_str() {
+            "I" => Some(Operand::Index),
+            "DT" => Some(Operand::DelayTimer),
+            "ST" => Some(Operand::SoundTimer),
+            _ => None,
+        }
+    }
+    /// The operand's current value; memory outside the CPU's reads as 0.
+    pub fn value(&self, cpu: &CPU) -> u16 {
+        match *self {
+            Operand::Register(x) => cpu.registers()[x as usize & 0xF] as u16,
+            Operand::Index => cpu.index(),
+            Operand::Memory(addr) => cpu.memory().get(addr).copied().unwrap_or(0) as u16,
+            Operand::DelayTimer => cpu.delay_timer() as u16,
+            Operand::SoundTimer => cpu.sound_timer() as u16,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(x) => write!(f, "V{:X}", x),
+            Operand::Index => write!(f, "I"),
+            Operand::Memory(addr) => write!(f, "[{:#05x}]", addr),
+            Operand::DelayTimer => write!(f, "DT"),
+            Operand::SoundTimer => write!(f, "ST"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    const SYMBOLS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Eq),
+        ("!=", Comparison::Ne),
+        ("<=", Comparison::Le),
+        (">=", Comparison::Ge),
+        ("<", Comparison::Lt),
+        (">", Comparison::Gt),
+    ];
+
+    fn symbol(&self) -> &'static str {
+        Self::SYMBOLS
+            .iter()
+            .find(|(_, cmp)| cmp == self)
+            .map_or("", |(symbol, _)| symbol)
+    }
+}
+
+/// A test such as `V3 == 0x10`, used to make a [`Breakpoint`] conditional.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub operand: Operand,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    /// Parses `<operand> <op> <value>`, where the operand is anything
+    /// [`Operand::parse`] accepts, `op` is one of `==`, `!=`, `<`, `<=`,
+    /// `>`, `>=` and the value is decimal, `0x` hex or `0b` binary.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (at, symbol, comparison) = Comparison::SYMBOLS
+            .iter()
+            .filter_map(|&(symbol, cmp)| s.find(symbol).map(|at| (at, symbol, cmp)))
+            .min_by_key(|&(at, symbol, _)| (at, usize::MAX - symbol.len()))?;
+        let operand = Operand::parse(&s[..at])?;
+        let value = parse_number(s[at + symbol.len()..].trim())?;
+        Some(Condition {
+            operand,
+            comparison,
+            value: u16::try_from(value).ok()?,
+        })
+    }
+    pub fn holds(&self, cpu: &CPU) -> bool {
+        let actual = self.operand.value(cpu);
+        match self.comparison {
+            Comparison::Eq => actual == self.value,
+            Comparison::Ne => actual != self.value,
+            Comparison::Lt => actual < self.value,
+            Comparison::Le => actual <= self.value,
+            Comparison::Gt => actual > self.value,
+            Comparison::Ge => actual >= self.value,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:#04x}",
+            self.operand,
+            self.comparison.symbol(),
+            self.value
+        )
+    }
+}
+
+/// Stops [`CPU::run`] before the instruction at `addr` executes, optionally
+/// only when `condition` holds at that point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: usize,
+    pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    pub fn new(addr: usize) -> Self {
+        Breakpoint {
+            addr,
+            condition: None,
+        }
+    }
+    pub fn when(self, condition: Condition) -> Self {
+        Breakpoint {
+            condition: Some(condition),
+            ..self
+        }
+    }
+    pub(crate) fn triggers(&self, cpu: &CPU) -> bool {
+        cpu.pc() == self.addr && self.condition.is_none_or(|c| c.holds(cpu))
+    }
+}
+
+/// Stops [`CPU::run`] after an instruction touches the watched location.
+///
+/// Instruction fetches don't count as reads; use a [`Breakpoint`] for that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watchpoint {
+    /// An instruction read the byte at this address.
+    Read(usize),
+    /// An instruction wrote the byte at this address, even if the value
+    /// stayed the same.
+    Write(usize),
+    /// The operand's value differs after an instruction from before it.
+    Change(Operand),
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watchpoint::Read(addr) => write!(f, "read of [{:#05x}]", addr),
+            Watchpoint::Write(addr) => write!(f, "write to [{:#05x}]", addr),
+            Watchpoint::Change(operand) => write!(f, "change of {}", operand),
+        }
+    }
+}
+
+/// A watchpoint that fired, with the value before and after the
+/// instruction (equal for reads).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    /// Address of the instruction that triggered it.
+    pub addr: usize,
+    pub old: u16,
+    pub new: u16,
+}
+
+/// Why [`CPU::run`] or [`CPU::run_for`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// HALT (0000) or SUPER-CHIP EXIT (00FD) was reached.
+    Halted,
+    /// FX0A is waiting for a key; update the keypad and run again.
+    WaitingForKey,
+    /// DXYN is waiting for a timer tick under [`Quirks::display_wait`] and
+    /// nothing paces the timers; call [`CPU::tick_timers`] and run again.
+    ///
+    /// [`Quirks::display_wait`]: crate::Quirks::display_wait
+    WaitingForVblank,
+    /// The program counter reached this breakpoint, which has not run yet.
+    Breakpoint(Breakpoint),
+    Watchpoint(WatchHit),
+    /// [`CPU::run_for`] executed as many instructions as it was allowed.
+    CycleLimit,
+}
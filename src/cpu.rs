@@ -0,0 +1,1263 @@
+use std::cell::Cell;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::asm::{assemble, assemble_file};
+use crate::breakpoint::{Breakpoint, StopReason, WatchHit, Watchpoint};
+use crate::display::Display;
+use crate::error::{CpuError, LoadError};
+use crate::font::{BIG_FONT, BIG_FONT_ADDR, BIG_FONT_GLYPH_SIZE, FONT, FONT_ADDR, FONT_GLYPH_SIZE};
+use crate::instruction::{decode, Instruction};
+use crate::keypad::Keypad;
+use crate::observer::Observer;
+use crate::platform::Platform;
+use crate::quirks::Quirks;
+use crate::rng::{RandomSource, XorShiftRng};
+use crate::PROGRAM_START;
+
+/// Call stack depth of the original COSMAC VIP interpreter.
+const DEFAULT_STACK_DEPTH: usize = 16;
+/// Memory of a classic CHIP-8 or SUPER-CHIP machine.
+pub const MEMORY_4K: usize = 0x1000;
+/// Memory of an XO-CHIP machine, the most a 16-bit address can reach.
+pub const MEMORY_64K: usize = 0x10000;
+/// FX3A pitch at which the audio pattern plays back at 4000 bits per second.
+const DEFAULT_PITCH: u8 = 64;
+/// Rate at which the delay and sound timers count down.
+pub const TIMER_HZ: u32 = 60;
+
+/// What happened during a single call to [`CPU::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepResult {
+    /// Address the instruction was fetched from.
+    pub addr: usize,
+    /// The opcode that was fetched and executed.
+    pub opcode: u16,
+    pub instruction: Instruction,
+    /// Program counter after execution.
+    pub pc: usize,
+    /// HALT (0000) was reached; the program counter stays on it.
+    pub halted: bool,
+    /// FX0A is still waiting for a key release; the instruction will run again on the next step.
+    pub waiting_for_key: bool,
+    /// DXYN is waiting for the next timer tick ([`Quirks::display_wait`]);
+    /// the instruction will run again on the next step.
+    pub waiting_for_vblank: bool,
+    /// The framebuffer was modified by 00E0 or DXYN.
+    pub screen_changed: bool,
+    /// V0-VF as they were before the instruction executed.
+    pub registers_before: [u8; 16],
+    /// The first watchpoint the instruction triggered, if any.
+    pub watch_hit: Option<WatchHit>,
+}
+
+/// What happens when the program counter or I runs past the end of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressOverflow {
+    /// Report [`CpuError::PcOutOfBounds`] or [`CpuError::MemoryFault`].
+    #[default]
+    Fault,
+    /// Wrap around to address 0, as the address lines of real hardware would.
+    Wrap,
+}
+
+/// Machine layout fixed when a [`CPU`] is constructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuConfig {
+    /// Bytes of memory, between 0x200 and [`MEMORY_64K`].
+    pub memory_size: usize,
+    /// Return addresses the call stack can hold.
+    pub stack_depth: usize,
+    pub address_overflow: AddressOverflow,
+}
+
+impl Default for CpuConfig {
+    fn default() -> Self {
+        CpuConfig {
+            memory_size: MEMORY_4K,
+            stack_depth: DEFAULT_STACK_DEPTH,
+            address_overflow: AddressOverflow::default(),
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+pub struct CPU {
+    registers: [u8; 16],
+    position_in_memory: usize,
+    memory: Vec<u8>,
+    address_overflow: AddressOverflow,
+    stack: Vec<u16>,
+    stack_pointer: usize,
+    index: u16,
+    delay_timer: u8,
+    sound_timer: u8,
+    instructions_per_second: Option<u32>,
+    timer_accumulator: u32,
+    vblank: bool,
+    keypad: Keypad,
+    display: Display,
+    rng: Box<dyn RandomSource>,
+    quirks: Quirks,
+    platform: Platform,
+    rpl_flags: [u8; 16],
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    observer: Option<Box<dyn Observer>>,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    /// First memory watchpoint hit by the instruction being executed, with
+    /// the byte's value before and after. A `Cell` because reads go
+    /// through `&self`.
+    memory_hit: Cell<Option<(Watchpoint, u16, u16)>>,
+    /// Address of the breakpoint that stopped the last run, which the next
+    /// run executes instead of stopping at again.
+    stopped_at: Option<usize>,
+}
+impl fmt::Debug for CPU {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CPU")
+            .field("registers", &self.registers)
+            .field("position_in_memory", &self.position_in_memory)
+            .field("index", &self.index)
+            .field("stack", &self.stack())
+            .field("stack_pointer", &self.stack_pointer)
+            .field("delay_timer", &self.delay_timer)
+            .field("sound_timer", &self.sound_timer)
+            .field("quirks", &self.quirks)
+            .field("platform", &self.platform)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CPU {
+    pub fn new() -> Self {
+        Self::with_config(CpuConfig::default())
+    }
+    /// Creates a CPU whose call stack holds `depth` return addresses.
+    /// SCHIP and XO-CHIP programs may need more than the classic 16.
+    pub fn with_stack_depth(depth: usize) -> Self {
+        Self::with_config(CpuConfig {
+            stack_depth: depth,
+            ..CpuConfig::default()
+        })
+    }
+    /// Creates a CPU with `size` bytes of memory, e.g. [`MEMORY_64K`] for
+    /// XO-CHIP.
+    pub fn with_memory_size(size: usize) -> Self {
+        Self::with_config(CpuConfig {
+            memory_size: size,
+            ..CpuConfig::default()
+        })
+    }
+    /// # Panics
+    ///
+    /// If `config.memory_size` is smaller than 0x200 (no room for the
+    /// interpreter area) or larger than [`MEMORY_64K`].
+    pub fn with_config(config: CpuConfig) -> Self {
+        assert!(
+            (PROGRAM_START..=MEMORY_64K).contains(&config.memory_size),
+            "memory size must be between {:#x} and {:#x} bytes",
+            PROGRAM_START,
+            MEMORY_64K
+        );
+        let mut cpu = CPU {
+            registers: [0; 16],
+            memory: vec![0; config.memory_size],
+            address_overflow: config.address_overflow,
+            position_in_memory: 0,
+            stack: vec![0; config.stack_depth],
+            stack_pointer: 0,
+            index: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            instructions_per_second: None,
+            timer_accumulator: 0,
+            vblank: false,
+            keypad: Keypad::new(),
+            display: Display::new(),
+            rng: Box::new(XorShiftRng::default()),
+            quirks: Quirks::default(),
+            platform: Platform::default(),
+            rpl_flags: [0; 16],
+            audio_pattern: [0; 16],
+            pitch: DEFAULT_PITCH,
+            observer: None,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            memory_hit: Cell::new(None),
+            stopped_at: None,
+        };
+        cpu.memory[FONT_ADDR..FONT_ADDR + FONT.len()].copy_from_slice(&FONT);
+        cpu.memory[BIG_FONT_ADDR..BIG_FONT_ADDR + BIG_FONT.len()].copy_from_slice(&BIG_FONT);
+        cpu
+    }
+    /// Copies `rom` into memory at 0x200 and points the program counter at it.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        let max = self.memory.len() - PROGRAM_START;
+        if rom.len() > max {
+            return Err(LoadError::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        self.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        self.position_in_memory = PROGRAM_START;
+        Ok(())
+    }
+    pub fn load_rom_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LoadError> {
+        let rom = fs::read(path)?;
+        self.load_rom(&rom)
+    }
+    /// Assembles `source` with [`assemble`] and loads the result like
+    /// [`CPU::load_rom`].
+    pub fn load_asm(&mut self, source: &str) -> Result<(), LoadError> {
+        let rom = assemble(source)?;
+        self.load_rom(&rom)
+    }
+    pub fn load_asm_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LoadError> {
+        let rom = assemble_file(path)?;
+        self.load_rom(&rom)
+    }
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+    pub fn registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.registers
+    }
+    pub fn pc(&self) -> usize {
+        self.position_in_memory
+    }
+    pub fn set_pc(&mut self, pc: usize) {
+        self.position_in_memory = pc;
+    }
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+    pub fn set_index(&mut self, index: u16) {
+        self.index = index;
+    }
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+    /// Return addresses currently on the call stack, innermost last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer]
+    }
+    pub fn stack_pointer(&self) -> usize {
+        self.stack_pointer
+    }
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+    /// The buzzer sounds for as long as the sound timer is non-zero.
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+    /// Decrements the delay and sound timers by one. Frontends call this once
+    /// per 60 Hz frame, independent of how many instructions they execute.
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.vblank = true;
+    }
+    /// With `Some(ips)`, `step` ticks the timers itself as if `ips`
+    /// instructions ran per second, for headless runs with no frame clock.
+    /// With `None` (the default) the host must call [`CPU::tick_timers`].
+    pub fn set_instructions_per_second(&mut self, ips: Option<u32>) {
+        self.instructions_per_second = ips.filter(|&ips| ips > 0);
+        self.timer_accumulator = 0;
+    }
+    pub fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+    pub fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+    /// With [`Quirks::display_wait`] set, DXYN only draws after a timer
+    /// tick. Either enable [`CPU::set_instructions_per_second`], or call
+    /// [`CPU::tick_timers`] whenever `run` returns
+    /// [`StopReason::WaitingForVblank`].
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.display.set_wrap(quirks.wrap_sprites);
+    }
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+    /// Selects the instruction set. Memory keeps the size chosen at
+    /// construction; pass [`Platform::memory_size`] to [`CPU::with_config`]
+    /// for the platform's full address space (64 KB on XO-CHIP).
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+    }
+    /// The 16-byte, 1-bit XO-CHIP audio pattern loaded by F002. It plays
+    /// while the sound timer is non-zero.
+    pub fn audio_pattern(&self) -> &[u8; 16] {
+        &self.audio_pattern
+    }
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+    /// Bits per second at which [`CPU::audio_pattern`] plays back, as set by
+    /// FX3A.
+    pub fn audio_sample_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - DEFAULT_PITCH as f64) / 48.0)
+    }
+    /// The SUPER-CHIP RPL user flags written by FX75. Hosts that want them to
+    /// survive between runs save and restore them here.
+    pub fn rpl_flags(&self) -> &[u8; 16] {
+        &self.rpl_flags
+    }
+    pub fn set_rpl_flags(&mut self, flags: [u8; 16]) {
+        self.rpl_flags = flags;
+    }
+    /// Replaces the generator CXNN draws from.
+    pub fn set_rng(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
+    /// Restarts the built-in generator from `seed`. CPUs start with a fixed
+    /// seed, so runs are reproducible unless reseeded.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Box::new(XorShiftRng::new(seed));
+    }
+    /// Attaches a hook that is called after every executed instruction.
+    pub fn set_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observer = Some(observer);
+    }
+    pub fn take_observer(&mut self) -> Option<Box<dyn Observer>> {
+        self.observer.take()
+    }
+    /// Makes [`CPU::run`] and [`CPU::run_for`] stop before executing the
+    /// breakpoint's instruction.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+    /// Removes every breakpoint at `addr`; returns whether there were any.
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|b| b.addr != addr);
+        self.breakpoints.len() != len
+    }
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+    /// Makes [`CPU::run`] and [`CPU::run_for`] stop after an instruction
+    /// that triggers the watchpoint. [`CPU::step`] reports hits in
+    /// [`StepResult::watch_hit`].
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+    /// Returns whether the watchpoint was set.
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| *w != watchpoint);
+        self.watchpoints.len() != len
+    }
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+    pub fn address_overflow(&self) -> AddressOverflow {
+        self.address_overflow
+    }
+    /// Fetches the opcode at the program counter without executing it.
+    pub fn read_opcode(&self) -> Result<u16, CpuError> {
+        let p = self.position_in_memory;
+        let len = self.memory.len();
+        let (hi, lo) = match self.address_overflow {
+            AddressOverflow::Fault if p >= len - 1 => {
+                return Err(CpuError::PcOutOfBounds { pc: p });
+            }
+            AddressOverflow::Fault => (p, p + 1),
+            AddressOverflow::Wrap => (p % len, (p % len + 1) % len),
+        };
+        let op_byte1 = self.memory[hi] as u16;
+        let op_byte2 = self.memory[lo] as u16;
+        Ok(op_byte1 << 8 | op_byte2)
+    }
+    /// Maps an address computed from I onto memory.
+    fn resolve(&self, addr: usize) -> Result<usize, CpuError> {
+        match self.address_overflow {
+            AddressOverflow::Fault if addr >= self.memory.len() => {
+                Err(CpuError::MemoryFault { addr })
+            }
+            AddressOverflow::Fault => Ok(addr),
+            AddressOverflow::Wrap => Ok(addr % self.memory.len()),
+        }
+    }
+    fn read_byte(&self, addr: usize) -> Result<u8, CpuError> {
+        let addr = self.resolve(addr)?;
+        let val = self.memory[addr];
+        self.note_access(Watchpoint::Read(addr), val, val);
+        Ok(val)
+    }
+    fn write_byte(&mut self, addr: usize, val: u8) -> Result<(), CpuError> {
+        let addr = self.resolve(addr)?;
+        self.note_access(Watchpoint::Write(addr), self.memory[addr], val);
+        self.memory[addr] = val;
+        Ok(())
+    }
+    fn note_access(&self, access: Watchpoint, old: u8, new: u8) {
+        if self.memory_hit.get().is_none() && self.watchpoints.contains(&access) {
+            self.memory_hit.set(Some((access, old as u16, new as u16)));
+        }
+    }
+    /// Runs until HALT, a breakpoint or watchpoint, or until FX0A blocks
+    /// waiting for a key. In the latter case the host should update the
+    /// keypad and call `run` again.
+    ///
+    /// Calling `run` again after it stopped at a breakpoint continues past
+    /// that breakpoint, unless the program counter was moved meanwhile.
+    pub fn run(&mut self) -> Result<StopReason, CpuError> {
+        self.run_until(None, self.stopped_at)
+    }
+    /// Like [`CPU::run`], but returns [`StopReason::CycleLimit`] after
+    /// `limit` instructions.
+    pub fn run_for(&mut self, limit: u64) -> Result<StopReason, CpuError> {
+        self.run_until(Some(limit), self.stopped_at)
+    }
+    /// Like [`CPU::run_for`], but always executes the instruction at the
+    /// program counter, even if a breakpoint is set there, as a debugger's
+    /// single step should.
+    pub fn step_for(&mut self, limit: u64) -> Result<StopReason, CpuError> {
+        self.run_until(Some(limit), Some(self.position_in_memory))
+    }
+    /// Breakpoints at `resumed_from` are ignored for the first instruction.
+    fn run_until(
+        &mut self,
+        limit: Option<u64>,
+        resumed_from: Option<usize>,
+    ) -> Result<StopReason, CpuError> {
+        let mut executed = 0;
+        loop {
+            if limit.is_some_and(|limit| executed >= limit) {
+                return Ok(StopReason::CycleLimit);
+            }
+            if executed > 0 || resumed_from != Some(self.position_in_memory) {
+                if let Some(breakpoint) = self.breakpoints.iter().find(|b| b.triggers(self)) {
+                    self.stopped_at = Some(breakpoint.addr);
+                    return Ok(StopReason::Breakpoint(*breakpoint));
+                }
+            }
+            let result = self.step()?;
+            executed += 1;
+            if let Some(hit) = result.watch_hit {
+                return Ok(StopReason::Watchpoint(hit));
+            }
+            if result.halted {
+                return Ok(StopReason::Halted);
+            }
+            if result.waiting_for_key {
+                return Ok(StopReason::WaitingForKey);
+            }
+            // Without instruction pacing only the host can end the wait.
+            if result.waiting_for_vblank && self.instructions_per_second.is_none() {
+                return Ok(StopReason::WaitingForVblank);
+            }
+        }
+    }
+    /// Fetches, decodes and executes exactly one instruction.
+    pub fn step(&mut self) -> Result<StepResult, CpuError> {
+        self.stopped_at = None;
+        let opcode = self.read_opcode()?;
+        if self.address_overflow == AddressOverflow::Wrap {
+            self.position_in_memory %= self.memory.len();
+        }
+        // The fetch succeeded, so this lies inside memory and fits in a u16.
+        let addr = self.position_in_memory;
+        self.position_in_memory += 2;
+        let mut result = StepResult {
+            addr,
+            opcode,
+            instruction: decode(opcode),
+            pc: 0,
+            halted: false,
+            waiting_for_key: false,
+            waiting_for_vblank: false,
+            screen_changed: false,
+            registers_before: self.registers,
+            watch_hit: None,
+        };
+        self.memory_hit.set(None);
+        let watched_before: Vec<u16> = self
+            .watchpoints
+            .iter()
+            .map(|w| match w {
+                Watchpoint::Change(operand) => operand.value(self),
+                _ => 0,
+            })
+            .collect();
+        if !self.platform.supports(&result.instruction) {
+            // Extensions that took over part of the 0NNN space (00CN, 00DN,
+            // 00FB-00FF) are still ignored machine-code calls elsewhere.
+            if opcode & 0xF000 != 0 {
+                return Err(CpuError::IllegalOpcode {
+                    addr: addr as u16,
+                    opcode,
+                });
+            }
+            result.instruction = Instruction::Sys { nnn: opcode };
+        }
+        match result.instruction {
+            Instruction::Halt | Instruction::Exit => {
+                self.position_in_memory = addr;
+                result.halted = true;
+            }
+            Instruction::ScrollDown { n } => {
+                self.display.scroll_down(n as usize);
+                result.screen_changed = true;
+            }
+            Instruction::ScrollUp { n } => {
+                self.display.scroll_up(n as usize);
+                result.screen_changed = true;
+            }
+            Instruction::ScrollRight => {
+                self.display.scroll_right(4);
+                result.screen_changed = true;
+            }
+            Instruction::ScrollLeft => {
+                self.display.scroll_left(4);
+                result.screen_changed = true;
+            }
+            Instruction::LowRes => {
+                self.display.set_hires(false);
+                result.screen_changed = true;
+            }
+            Instruction::HighRes => {
+                self.display.set_hires(true);
+                result.screen_changed = true;
+            }
+            Instruction::ClearScreen => {
+                self.display.clear();
+                result.screen_changed = true;
+            }
+            Instruction::Return => self.ret(addr as u16)?,
+            Instruction::Sys { .. } => {}
+            Instruction::Jump { nnn } => self.jump(nnn),
+            Instruction::Call { nnn } => self.call(addr as u16, nnn)?,
+            Instruction::SkipEqImm { x, nn } => self.skip_if(self.registers[x as usize] == nn),
+            Instruction::SkipNeImm { x, nn } => self.skip_if(self.registers[x as usize] != nn),
+            Instruction::SkipEqReg { x, y } => {
+                self.skip_if(self.registers[x as usize] == self.registers[y as usize])
+            }
+            Instruction::SaveRange { x, y } => {
+                for (offset, r) in Self::register_range(x, y).enumerate() {
+                    self.write_byte(self.index as usize + offset, self.registers[r])?;
+                }
+            }
+            Instruction::LoadRange { x, y } => {
+                for (offset, r) in Self::register_range(x, y).enumerate() {
+                    self.registers[r] = self.read_byte(self.index as usize + offset)?;
+                }
+            }
+            Instruction::LoadImm { x, nn } => self.registers[x as usize] = nn,
+            Instruction::AddImm { x, nn } => {
+                self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn)
+            }
+            Instruction::Move { x, y } => self.registers[x as usize] = self.registers[y as usize],
+            Instruction::Or { x, y } => self.logic_xy(x, y, |a, b| a | b),
+            Instruction::And { x, y } => self.logic_xy(x, y, |a, b| a & b),
+            Instruction::Xor { x, y } => self.logic_xy(x, y, |a, b| a ^ b),
+            Instruction::Add { x, y } => self.add_xy(x, y),
+            Instruction::Sub { x, y } => self.sub_xy(x, y),
+            Instruction::ShiftRight { x, y } => self.shr_xy(x, y),
+            Instruction::SubN { x, y } => self.subn_xy(x, y),
+            Instruction::ShiftLeft { x, y } => self.shl_xy(x, y),
+            Instruction::SkipNeReg { x, y } => {
+                self.skip_if(self.registers[x as usize] != self.registers[y as usize])
+            }
+            Instruction::LoadIndex { nnn } => self.index = nnn,
+            Instruction::JumpOffset { x, nnn } => self.jump_offset(x, nnn),
+            Instruction::Random { x, nn } => self.registers[x as usize] = self.rng.next_byte() & nn,
+            Instruction::Draw { x, y, n } => {
+                if self.quirks.display_wait && !self.vblank {
+                    self.position_in_memory -= 2;
+                    result.waiting_for_vblank = true;
+                } else {
+                    self.draw(x, y, n)?;
+                    self.vblank = false;
+                    result.screen_changed = true;
+                }
+            }
+            Instruction::SkipKeyPressed { x } => self.skip_if(self.key_pressed(x)),
+            Instruction::SkipKeyNotPressed { x } => self.skip_if(!self.key_pressed(x)),
+            Instruction::LongLoadIndex => {
+                self.index = self.read_opcode()?;
+                self.position_in_memory += 2;
+            }
+            Instruction::SelectPlanes { n } => self.display.select_planes(n),
+            Instruction::LoadAudio => {
+                let start = self.index as usize;
+                for i in 0..self.audio_pattern.len() {
+                    self.audio_pattern[i] = self.read_byte(start + i)?;
+                }
+            }
+            Instruction::SetPitch { x } => self.pitch = self.registers[x as usize],
+            Instruction::LoadDelay { x } => self.registers[x as usize] = self.delay_timer,
+            Instruction::WaitKey { x } => result.waiting_for_key = !self.wait_for_key(x),
+            Instruction::SetDelay { x } => self.delay_timer = self.registers[x as usize],
+            Instruction::SetSound { x } => self.sound_timer = self.registers[x as usize],
+            Instruction::AddIndex { x } => self.add_index(x),
+            Instruction::LoadFont { x } => {
+                let glyph = (self.registers[x as usize] & 0xF) as usize;
+                self.index = (FONT_ADDR + glyph * FONT_GLYPH_SIZE) as u16;
+            }
+            Instruction::LoadBigFont { x } => {
+                let glyph = (self.registers[x as usize] & 0xF) as usize;
+                self.index = (BIG_FONT_ADDR + glyph * BIG_FONT_GLYPH_SIZE) as u16;
+            }
+            Instruction::StoreBcd { x } => self.store_bcd(x)?,
+            Instruction::StoreRegisters { x } => self.store_registers(x)?,
+            Instruction::LoadRegisters { x } => self.load_registers(x)?,
+            Instruction::SaveFlags { x } => {
+                let n = x as usize + 1;
+                self.rpl_flags[..n].copy_from_slice(&self.registers[..n]);
+            }
+            Instruction::LoadFlags { x } => {
+                let n = x as usize + 1;
+                self.registers[..n].copy_from_slice(&self.rpl_flags[..n]);
+            }
+            Instruction::Illegal(_) => {
+                return Err(CpuError::IllegalOpcode {
+                    addr: addr as u16,
+                    opcode,
+                })
+            }
+        }
+        if self.address_overflow == AddressOverflow::Wrap {
+            self.position_in_memory %= self.memory.len();
+        }
+        result.pc = self.position_in_memory;
+        if let Some(ips) = self.instructions_per_second {
+            self.timer_accumulator += TIMER_HZ;
+            while self.timer_accumulator >= ips {
+                self.timer_accumulator -= ips;
+                self.tick_timers();
+            }
+        }
+        result.watch_hit = self
+            .memory_hit
+            .take()
+            .or_else(|| {
+                self.watchpoints
+                    .iter()
+                    .zip(watched_before)
+                    .find_map(|(watchpoint, old)| match watchpoint {
+                        Watchpoint::Change(operand) if operand.value(self) != old => {
+                            Some((*watchpoint, old, operand.value(self)))
+                        }
+                        _ => None,
+                    })
+            })
+            .map(|(watchpoint, old, new)| WatchHit {
+                watchpoint,
+                addr,
+                old,
+                new,
+            });
+        if let Some(mut observer) = self.observer.take() {
+            observer.on_step(self, &result);
+            self.observer = Some(observer);
+        }
+        Ok(result)
+    }
+    /// `from` is the address of the CALL itself, reported on overflow.
+    fn call(&mut self, from: u16, addr: u16) -> Result<(), CpuError> {
+        let sp = self.stack_pointer;
+        if sp >= self.stack.len() {
+            return Err(CpuError::StackOverflow { addr: from });
+        }
+        let len = self.memory.len();
+        let return_addr = match self.address_overflow {
+            AddressOverflow::Fault if self.position_in_memory >= len => {
+                return Err(CpuError::PcOutOfBounds {
+                    pc: self.position_in_memory,
+                });
+            }
+            AddressOverflow::Fault => self.position_in_memory,
+            AddressOverflow::Wrap => self.position_in_memory % len,
+        };
+        self.stack[sp] = return_addr as u16;
+        self.stack_pointer += 1;
+        self.position_in_memory = addr as usize;
+        Ok(())
+    }
+    fn ret(&mut self, from: u16) -> Result<(), CpuError> {
+        if self.stack_pointer == 0 {
+            return Err(CpuError::StackUnderflow { addr: from });
+        }
+        self.stack_pointer -= 1;
+        let call_addr = self.stack[self.stack_pointer];
+        self.position_in_memory = call_addr as usize;
+        Ok(())
+    }
+    fn jump(&mut self, addr: u16) {
+        self.position_in_memory = addr as usize;
+    }
+    fn jump_offset(&mut self, x: u8, addr: u16) {
+        let offset = if self.quirks.jump_uses_vx {
+            self.registers[x as usize]
+        } else {
+            self.registers[0]
+        };
+        self.jump(addr + offset as u16);
+    }
+    /// XO-CHIP skips over both words of F000 NNNN.
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            let long = self.platform == Platform::XoChip && self.read_opcode() == Ok(0xF000);
+            self.position_in_memory += if long { 4 } else { 2 };
+        }
+    }
+    /// VX..=VY for 5XY2/5XY3, counting down when X > Y.
+    fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y) = (x as usize, y as usize);
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+    fn add_index(&mut self, x: u8) {
+        self.index = self.index.wrapping_add(self.registers[x as usize] as u16);
+        if self.quirks.index_overflow_sets_vf {
+            self.registers[0xF] = if self.index > 0x0FFF { 1 } else { 0 };
+        }
+    }
+    fn logic_xy(&mut self, x: u8, y: u8, op: fn(u8, u8) -> u8) {
+        self.registers[x as usize] = op(self.registers[x as usize], self.registers[y as usize]);
+        if self.quirks.logic_resets_vf {
+            self.registers[0xF] = 0;
+        }
+    }
+    fn add_xy(&mut self, x: u8, y: u8) {
+        let arg1 = self.registers[x as usize];
+        let arg2 = self.registers[y as usize];
+
+        let (val, overflow) = arg1.overflowing_add(arg2);
+
+        self.registers[x as usize] = val;
+        if overflow {
+            self.registers[0xF] = 1;
+        } else {
+            self.registers[0xF] = 0;
+        }
+    }
+    fn sub_xy(&mut self, x: u8, y: u8) {
+        let arg1 = self.registers[x as usize];
+        let arg2 = self.registers[y as usize];
+
+        let (val, borrow) = arg1.overflowing_sub(arg2);
+        self.registers[x as usize] = val;
+        self.registers[0xF] = if borrow { 0 } else { 1 };
+    }
+    fn subn_xy(&mut self, x: u8, y: u8) {
+        let arg1 = self.registers[x as usize];
+        let arg2 = self.registers[y as usize];
+
+        let (val, borrow) = arg2.overflowing_sub(arg1);
+        self.registers[x as usize] = val;
+        self.registers[0xF] = if borrow { 0 } else { 1 };
+    }
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.registers[y as usize]
+        } else {
+            self.registers[x as usize]
+        }
+    }
+    fn shr_xy(&mut self, x: u8, y: u8) {
+        let arg = self.shift_source(x, y);
+        self.registers[x as usize] = arg >> 1;
+        self.registers[0xF] = arg & 0x1;
+    }
+    fn shl_xy(&mut self, x: u8, y: u8) {
+        let arg = self.shift_source(x, y);
+        self.registers[x as usize] = arg << 1;
+        self.registers[0xF] = arg >> 7;
+    }
+    fn draw(&mut self, x: u8, y: u8, height: u8) -> Result<(), CpuError> {
+        let large = height == 0 && self.platform != Platform::Chip8;
+        let planes = self.display.selected_planes().count_ones() as usize;
+        let start = self.index as usize;
+        let len = planes * if large { 32 } else { height as usize };
+        let sprite = (start..start + len)
+            .map(|addr| self.read_byte(addr))
+            .collect::<Result<Vec<u8>, CpuError>>()?;
+        let vx = self.registers[x as usize] as usize;
+        let vy = self.registers[y as usize] as usize;
+        let collision = if large {
+            self.display.draw_sprite16(vx, vy, &sprite)
+        } else {
+            self.display.draw_sprite(vx, vy, &sprite)
+        };
+        self.registers[0xF] = if collision { 1 } else { 0 };
+        Ok(())
+    }
+    fn key_pressed(&self, x: u8) -> bool {
+        self.keypad.is_pressed(self.registers[x as usize])
+    }
+    /// Returns `false` until a key has been pressed and released, rewinding
+    /// so FX0A executes again.
+    fn wait_for_key(&mut self, x: u8) -> bool {
+        match self.keypad.poll_release() {
+            Some(key) => {
+                self.registers[x as usize] = key;
+                true
+            }
+            None => {
+                self.position_in_memory -= 2;
+                false
+            }
+        }
+    }
+    fn store_bcd(&mut self, x: u8) -> Result<(), CpuError> {
+        let val = self.registers[x as usize];
+        let i = self.index as usize;
+        self.write_byte(i, val / 100)?;
+        self.write_byte(i + 1, (val / 10) % 10)?;
+        self.write_byte(i + 2, val % 10)
+    }
+    fn store_registers(&mut self, x: u8) -> Result<(), CpuError> {
+        let i = self.index as usize;
+        for r in 0..=x as usize {
+            self.write_byte(i + r, self.registers[r])?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.index = self.index.wrapping_add(x as u16 + 1);
+        }
+        Ok(())
+    }
+    fn load_registers(&mut self, x: u8) -> Result<(), CpuError> {
+        let i = self.index as usize;
+        for r in 0..=x as usize {
+            self.registers[r] = self.read_byte(i + r)?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.index = self.index.wrapping_add(x as u16 + 1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::breakpoint::{Condition, Operand};
+
+    fn run_alu(opcode: u16, vx: u8, vy: u8) -> (u8, u8) {
+        let mut cpu = CPU::new();
+        cpu.registers[0x1] = vx;
+        cpu.registers[0x2] = vy;
+        cpu.memory[0] = (opcode >> 8) as u8;
+        cpu.memory[1] = opcode as u8;
+        cpu.run().unwrap();
+        (cpu.registers[0x1], cpu.registers[0xF])
+    }
+
+    #[test]
+    fn sub_xy_stores_vx_minus_vy_in_vx() {
+        // (VX, VY, VX - VY, VF)
+        let vectors = [
+            (10, 3, 7, 1),
+            (3, 10, 249, 0),
+            (5, 5, 0, 1),
+            (0, 1, 255, 0),
+            (255, 0, 255, 1),
+        ];
+        for (vx, vy, result, vf) in vectors {
+            assert_eq!(run_alu(0x8125, vx, vy), (result, vf), "{} - {}", vx, vy);
+        }
+    }
+
+    #[test]
+    fn subn_xy_stores_vy_minus_vx_in_vx() {
+        // (VX, VY, VY - VX, VF)
+        let vectors = [
+            (3, 10, 7, 1),
+            (10, 3, 249, 0),
+            (5, 5, 0, 1),
+            (1, 0, 255, 0),
+            (0, 255, 255, 1),
+        ];
+        for (vx, vy, result, vf) in vectors {
+            assert_eq!(run_alu(0x8127, vx, vy), (result, vf), "{} - {}", vy, vx);
+        }
+    }
+
+    #[test]
+    fn sub_xy_leaves_vy_untouched() {
+        let mut cpu = CPU::new();
+        cpu.registers[0x1] = 10;
+        cpu.registers[0x2] = 3;
+        cpu.memory[..2].copy_from_slice(&[0x81, 0x25]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0x2], 3);
+    }
+
+    #[test]
+    fn sub_flag_wins_when_vf_is_destination() {
+        let mut cpu = CPU::new();
+        cpu.registers[0xF] = 10;
+        cpu.registers[0x1] = 3;
+        cpu.memory[..4].copy_from_slice(&[0x8F, 0x15, 0x00, 0x00]);
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    /// Steps a CALL to itself until the stack overflows, returning how many
+    /// calls succeeded and the error.
+    fn nest_calls(mut cpu: CPU) -> (usize, CpuError) {
+        cpu.load_rom(&[0x22, 0x00]).unwrap();
+        let mut calls = 0;
+        loop {
+            match cpu.step() {
+                Ok(_) => calls += 1,
+                Err(error) => return (calls, error),
+            }
+        }
+    }
+
+    #[test]
+    fn seventeenth_nested_call_overflows_the_default_stack() {
+        assert_eq!(
+            nest_calls(CPU::new()),
+            (16, CpuError::StackOverflow { addr: 0x200 })
+        );
+    }
+
+    #[test]
+    fn with_stack_depth_allows_exactly_that_many_nested_calls() {
+        for depth in [1, 16, 64] {
+            let (calls, error) = nest_calls(CPU::with_stack_depth(depth));
+            assert_eq!(calls, depth);
+            assert_eq!(error, CpuError::StackOverflow { addr: 0x200 });
+        }
+    }
+
+    #[test]
+    fn stack_errors_report_the_instruction_address() {
+        let mut cpu = CPU::with_config(CpuConfig {
+            memory_size: MEMORY_64K,
+            ..CpuConfig::default()
+        });
+        cpu.memory[0xFFFE..].copy_from_slice(&[0x00, 0xEE]);
+        cpu.set_pc(0xFFFE);
+        assert_eq!(
+            cpu.step().unwrap_err(),
+            CpuError::StackUnderflow { addr: 0xFFFE }
+        );
+    }
+
+    #[test]
+    fn call_at_the_end_of_memory_faults_or_wraps_its_return_address() {
+        for (address_overflow, expected) in [
+            (
+                AddressOverflow::Fault,
+                Err(CpuError::PcOutOfBounds { pc: 0x10000 }),
+            ),
+            (AddressOverflow::Wrap, Ok(0)),
+        ] {
+            let mut cpu = CPU::with_config(CpuConfig {
+                memory_size: MEMORY_64K,
+                address_overflow,
+                ..CpuConfig::default()
+            });
+            cpu.memory[0xFFFE..].copy_from_slice(&[0x23, 0x00]);
+            cpu.memory[0x300..0x302].copy_from_slice(&[0x00, 0xEE]);
+            cpu.set_pc(0xFFFE);
+            let returned_to = cpu.step().and_then(|_| cpu.step()).map(|r| r.pc);
+            assert_eq!(returned_to, expected, "{:?}", address_overflow);
+        }
+    }
+
+    #[test]
+    fn out_of_range_pc_faults_instead_of_overflowing() {
+        let mut cpu = CPU::new();
+        cpu.set_pc(usize::MAX);
+        assert_eq!(
+            cpu.step().unwrap_err(),
+            CpuError::PcOutOfBounds { pc: usize::MAX }
+        );
+    }
+
+    #[test]
+    fn extension_sys_opcodes_are_ignored_on_chip8() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x00, 0xC0, 0x00, 0xFD, 0x00, 0xFF, 0x00, 0x00])
+            .unwrap();
+        assert_eq!(cpu.run().unwrap(), StopReason::Halted);
+        assert_eq!(cpu.pc(), 0x206);
+    }
+
+    fn on_platform(platform: Platform, source: &str) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.set_platform(platform);
+        cpu.load_asm(source).unwrap();
+        cpu
+    }
+
+    fn lit_pixels(display: &Display) -> Vec<(usize, usize)> {
+        (0..display.height())
+            .flat_map(|y| (0..display.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| display.pixel(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn schip_high_and_low_switch_the_resolution() {
+        let mut cpu = on_platform(Platform::SuperChip, "HIGH\nLOW");
+        cpu.step().unwrap();
+        assert_eq!((cpu.display().width(), cpu.display().height()), (128, 64));
+        cpu.step().unwrap();
+        assert_eq!((cpu.display().width(), cpu.display().height()), (64, 32));
+    }
+
+    #[test]
+    fn schip_scrolls_move_the_picture() {
+        let mut cpu = on_platform(
+            Platform::SuperChip,
+            "
+                  LD I, dot
+                  LD V0, 8
+                  DRW V0, V0, 1
+                  SCD 2
+                  SCR
+                  SCL
+            dot:  DB 0x80
+            ",
+        );
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        for expected in [(8, 10), (12, 10), (8, 10)] {
+            cpu.step().unwrap();
+            assert_eq!(lit_pixels(cpu.display()), [expected]);
+        }
+    }
+
+    #[test]
+    fn schip_dxy0_draws_a_16x16_sprite() {
+        let mut cpu = on_platform(
+            Platform::SuperChip,
+            "
+                  HIGH
+                  LD I, box
+                  DRW V0, V0, 0
+                  HALT
+            box:  DW 0x8001, 0, 0, 0, 0, 0, 0, 0
+                  DW 0, 0, 0, 0, 0, 0, 0, 0x8001
+            ",
+        );
+        cpu.run().unwrap();
+        assert_eq!(
+            lit_pixels(cpu.display()),
+            [(0, 0), (15, 0), (0, 15), (15, 15)]
+        );
+    }
+
+    #[test]
+    fn schip_fx30_points_i_at_the_big_font() {
+        let mut cpu = on_platform(Platform::SuperChip, "LD V1, 7\nLD HF, V1");
+        cpu.run().unwrap();
+        assert_eq!(
+            cpu.index() as usize,
+            BIG_FONT_ADDR + 7 * BIG_FONT_GLYPH_SIZE
+        );
+    }
+
+    #[test]
+    fn schip_fx75_and_fx85_save_and_restore_the_flag_registers() {
+        let mut cpu = on_platform(
+            Platform::SuperChip,
+            "
+                LD V0, 1
+                LD V1, 2
+                LD V2, 3
+                LD R, V2
+                LD V0, 0
+                LD V1, 0
+                LD V2, 0
+                LD V1, R
+            ",
+        );
+        cpu.run().unwrap();
+        assert_eq!(cpu.registers[..3], [1, 2, 0]);
+    }
+
+    #[test]
+    fn xo_chip_skips_step_over_both_words_of_f000() {
+        for (skip, index) in [("SE", 0), ("SNE", 0x1234)] {
+            let mut cpu = on_platform(
+                Platform::XoChip,
+                &format!("{} V0, 0\nLD I, LONG 0x1234\nLD V1, 1", skip),
+            );
+            cpu.run().unwrap();
+            assert_eq!((cpu.index(), cpu.registers[0x1]), (index, 1), "{}", skip);
+        }
+    }
+
+    #[test]
+    fn xo_chip_draws_one_sprite_per_selected_plane() {
+        let mut cpu = on_platform(
+            Platform::XoChip,
+            "
+                  PLANE 3
+                  LD I, dots
+                  DRW V0, V0, 1
+                  HALT
+            dots: DB 0x80, 0x40
+            ",
+        );
+        cpu.run().unwrap();
+        let display = cpu.display();
+        assert_eq!((display.color(0, 0), display.color(1, 0)), (0b01, 0b10));
+    }
+
+    #[test]
+    fn xo_chip_register_ranges_run_backwards_when_x_is_above_y() {
+        let mut cpu = on_platform(
+            Platform::XoChip,
+            "
+                LD V1, 1
+                LD V2, 2
+                LD V3, 3
+                LD I, 0x300
+                SAVE V3, V1
+                LOAD V2, V0
+            ",
+        );
+        cpu.run().unwrap();
+        assert_eq!(cpu.memory[0x300..0x303], [3, 2, 1]);
+        assert_eq!(cpu.registers[..4], [1, 2, 3, 3]);
+    }
+
+    /// Counts V0 up forever, storing it at 0x300 each time round.
+    const COUNTER: &str = "
+        loop: ADD V0, 1
+              LD I, 0x300
+              LD [I], V0
+              JP loop
+    ";
+
+    #[test]
+    fn conditional_breakpoint_stops_only_when_condition_holds() {
+        let mut cpu = CPU::new();
+        cpu.load_asm(COUNTER).unwrap();
+        let condition = Condition::parse("V0 == 0x10").unwrap();
+        cpu.add_breakpoint(Breakpoint::new(0x202).when(condition));
+        let stop = cpu.run_for(1000).unwrap();
+        assert_eq!(
+            stop,
+            StopReason::Breakpoint(Breakpoint::new(0x202).when(condition))
+        );
+        assert_eq!((cpu.pc(), cpu.registers[0]), (0x202, 0x10));
+    }
+
+    #[test]
+    fn watchpoints_report_the_instruction_and_values() {
+        let mut cpu = CPU::new();
+        cpu.load_asm(COUNTER).unwrap();
+        cpu.add_watchpoint(Watchpoint::Write(0x300));
+        let hit = WatchHit {
+            watchpoint: Watchpoint::Write(0x300),
+            addr: 0x204,
+            old: 0,
+            new: 1,
+        };
+        assert_eq!(cpu.run().unwrap(), StopReason::Watchpoint(hit));
+
+        cpu.clear_watchpoints();
+        cpu.add_watchpoint(Watchpoint::Change(Operand::Register(0)));
+        let stop = cpu.run().unwrap();
+        assert!(matches!(
+            stop,
+            StopReason::Watchpoint(WatchHit {
+                addr: 0x200,
+                old: 1,
+                new: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn breakpoints_stop_the_first_run_and_are_resumed_past() {
+        let mut cpu = CPU::new();
+        cpu.load_asm(COUNTER).unwrap();
+        cpu.add_breakpoint(Breakpoint::new(0x200));
+        let stop = StopReason::Breakpoint(Breakpoint::new(0x200));
+        assert_eq!(cpu.run().unwrap(), stop);
+        assert_eq!(cpu.registers[0], 0);
+        assert_eq!(cpu.run().unwrap(), stop);
+        assert_eq!(cpu.registers[0], 1);
+    }
+
+    #[test]
+    fn display_wait_returns_until_the_host_ticks_the_timers() {
+        let mut cpu = CPU::new();
+        cpu.set_quirks(Quirks::cosmac_vip());
+        cpu.load_asm("DRW V0, V0, 1\nDRW V0, V0, 1").unwrap();
+        assert_eq!(cpu.run().unwrap(), StopReason::WaitingForVblank);
+        cpu.tick_timers();
+        assert_eq!(cpu.run().unwrap(), StopReason::WaitingForVblank);
+        assert_eq!(cpu.pc(), 0x202);
+        cpu.tick_timers();
+        assert_eq!(cpu.run().unwrap(), StopReason::Halted);
+    }
+
+    #[test]
+    fn step_for_executes_the_instruction_at_a_breakpoint() {
+        let mut cpu = CPU::new();
+        cpu.load_asm(COUNTER).unwrap();
+        cpu.add_breakpoint(Breakpoint::new(0x200));
+        assert_eq!(cpu.step_for(1).unwrap(), StopReason::CycleLimit);
+        assert_eq!((cpu.pc(), cpu.registers[0]), (0x202, 1));
+
+        // Breakpoints further on still stop a multi-instruction step.
+        cpu.clear_breakpoints();
+        let condition = Condition::parse("V0 <= 2").unwrap();
+        let breakpoint = Breakpoint::new(0x202).when(condition);
+        cpu.add_breakpoint(breakpoint);
+        assert_eq!(cpu.step_for(5).unwrap(), StopReason::Breakpoint(breakpoint));
+        assert_eq!((cpu.pc(), cpu.registers[0]), (0x202, 2));
+    }
+
+    #[test]
+    fn run_for_stops_at_the_cycle_limit() {
+        let mut cpu = CPU::new();
+        cpu.load_asm(COUNTER).unwrap();
+        assert_eq!(cpu.run_for(8).unwrap(), StopReason::CycleLimit);
+        assert_eq!(cpu.registers[0], 2);
+    }
+}
@@ -0,0 +1,333 @@
+//! The `--debug` REPL of the command-line runner.
+
+use std::io::{self, BufRead, Write};
+
+use cpu_emulator::{
+    disassemble_linear, parse_number, parse_register, Breakpoint, Condition, Line, LoadError,
+    Operand, StopReason, Watchpoint, CPU,
+};
+
+use crate::{print_display, print_registers};
+
+/// Instructions shown before the program counter by `disasm` without an address.
+const DISASM_CONTEXT: usize = 4;
+const DISASM_LINES: usize = 10;
+const HEXDUMP_BYTES: usize = 64;
+
+const HELP: &str = "\
+commands:
+  s, step [n]            execute n instructions (default: 1)
+  c, continue            run until a breakpoint, watchpoint, HALT or key wait
+  b, break <addr> [if <cond>]
+                         stop before executing the instruction at addr,
+                         optionally only when a condition like V3 == 0x10 holds
+  d, delete <addr>       remove the breakpoints at addr
+  w, watch <target>      stop after an instruction writes the byte at addr, or
+                         changes V0-VF, I, DT, ST or [addr]
+  rw, rwatch <addr>      stop after an instruction reads the byte at addr
+  unwatch <target>       remove the watchpoints on a target
+  l, list                list breakpoints and watchpoints
+  r, regs                print V0-VF, I, PC, SP and the timers
+  stack                  print the call stack
+  dis, disasm [addr] [n] disassemble n instructions (default: around PC)
+  x, hexdump <addr> [n]  dump n bytes of memory (default: 64)
+  set <reg> <value>      set V0-VF, I or PC
+  poke <addr> <byte>...  write bytes to memory
+  key <k>                press and release key k (0-F)
+  screen                 print the display
+  reset                  reload the ROM, keeping breakpoints and watchpoints
+  h, help                show this message
+  q, quit                exit
+An empty line repeats the previous step or continue.";
+
+struct Debugger<F> {
+    cpu: CPU,
+    reset: F,
+}
+
+/// Runs the debugger on `cpu` until the user quits or stdin closes.
+/// `reset` builds a fresh CPU with the ROM loaded, for the `reset` command.
+pub fn run<F: FnMut() -> Result<CPU, LoadError>>(cpu: CPU, reset: F) {
+    let mut debugger = Debugger { cpu, reset };
+    let mut previous = String::new();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    debugger.show_current();
+    loop {
+        print!("(chip8) ");
+        let _ = io::stdout().flush();
+        let Some(Ok(line)) = lines.next() else {
+            println!();
+            return;
+        };
+        let line = if line.trim().is_empty() {
+            previous.clone()
+        } else {
+            line
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            continue;
+        };
+        if matches!(command, "s" | "step" | "c" | "continue") {
+            previous = line.clone();
+        }
+        match debugger.execute(command, args) {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(msg) => println!("error: {}", msg),
+        }
+    }
+}
+
+impl<F: FnMut() -> Result<CPU, LoadError>> Debugger<F> {
+    /// Runs one command; returns `Ok(false)` when the user asked to quit.
+    fn execute(&mut self, command: &str, args: &[&str]) -> Result<bool, String> {
+        match (command, args) {
+            ("s" | "step", []) => self.resume(Some(1)),
+            ("s" | "step", [n]) => self.resume(Some(number(n)?)),
+            ("c" | "continue", []) => self.resume(None),
+            ("b" | "break", [addr]) => self.cpu.add_breakpoint(Breakpoint::new(address(addr)?)),
+            ("b" | "break", [addr, "if", condition @ ..]) => {
+                let condition = condition.join(" ");
+                let condition = Condition::parse(&condition)
+                    .ok_or(format!("invalid condition: {}", condition))?;
+                self.cpu
+                    .add_breakpoint(Breakpoint::new(address(addr)?).when(condition));
+            }
+            ("d" | "delete", [addr]) => {
+                if !self.cpu.remove_breakpoint(address(addr)?) {
+                    return Err(format!("no breakpoint at {}", addr));
+                }
+            }
+            ("w" | "watch", [target]) => {
+                let watchpoint = match Operand::parse(target) {
+                    Some(operand) => Watchpoint::Change(operand),
+                    None => Watchpoint::Write(address(target)?),
+                };
+                self.cpu.add_watchpoint(watchpoint);
+            }
+            ("rw" | "rwatch", [addr]) => self.cpu.add_watchpoint(Watchpoint::Read(address(addr)?)),
+            ("unwatch", [target]) => {
+                let operand = Operand::parse(target);
+                let addr = parse_number(target).map(|addr| addr as usize);
+                let watchpoints: Vec<Watchpoint> = self
+                    .cpu
+                    .watchpoints()
+                    .iter()
+                    .copied()
+                    .filter(|w| match *w {
+                        Watchpoint::Change(o) => Some(o) == operand,
+                        Watchpoint::Read(a) | Watchpoint::Write(a) => Some(a) == addr,
+                    })
+                    .collect();
+                if watchpoints.is_empty() {
+                    return Err(format!("no watchpoint on {}", target));
+                }
+                for watchpoint in watchpoints {
+                    self.cpu.remove_watchpoint(watchpoint);
+                }
+            }
+            ("l" | "list", []) => {
+                for breakpoint in self.cpu.breakpoints() {
+                    match breakpoint.condition {
+                        Some(condition) => {
+                            println!("break {:03x} if {}", breakpoint.addr, condition)
+                        }
+                        None => println!("break {:03x}", breakpoint.addr),
+                    }
+                }
+                for watchpoint in self.cpu.watchpoints() {
+                    println!("watch {}", watchpoint);
+                }
+            }
+            ("r" | "regs", []) => print_registers(&self.cpu),
+            ("stack", []) => {
+                for (depth, addr) in self.cpu.stack().iter().enumerate().rev() {
+                    println!("#{} {:03x}", depth, addr);
+                }
+            }
+            ("dis" | "disasm", []) => {
+                let start = self.cpu.pc().saturating_sub(DISASM_CONTEXT * 2);
+                self.disassemble(start, DISASM_LINES)?;
+            }
+            ("dis" | "disasm", [addr]) => self.disassemble(address(addr)?, DISASM_LINES)?,
+            ("dis" | "disasm", [addr, n]) => {
+                self.disassemble(address(addr)?, number(n)? as usize)?
+            }
+            ("x" | "hexdump", [addr]) => self.hexdump(address(addr)?, HEXDUMP_BYTES)?,
+            ("x" | "hexdump", [addr, n]) => self.hexdump(address(addr)?, number(n)? as usize)?,
+            ("set", [target, value]) => {
+                let value = number(value)?;
+                match target.to_ascii_lowercase().as_str() {
+                    "pc" => self.cpu.set_pc(value as usize),
+                    "i" => self.cpu.set_index(fits(value, target)?),
+                    _ => {
+                        let x = parse_register(target)
+                            .ok_or(format!("unknown register: {}", target))?;
+                        self.cpu.registers_mut()[x as usize] = fits(value, target)?;
+                    }
+                }
+            }
+            ("poke", [addr, bytes @ ..]) if !bytes.is_empty() => {
+                let addr = address(addr)?;
+                let bytes = bytes
+                    .iter()
+                    .map(|b| number(b).and_then(|value| fits(value, "a byte")))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let memory = self.cpu.memory_mut();
+                let end = addr
+                    .checked_add(bytes.len())
+                    .filter(|&end| end <= memory.len())
+                    .ok_or(format!(
+                        "{} bytes at {:03x} run past the end of memory",
+                        bytes.len(),
+                        addr
+                    ))?;
+                memory[addr..end].copy_from_slice(&bytes);
+            }
+            ("key", [k]) => {
+                let k = number(k)?;
+                if k > 0xF {
+                    return Err(format!("invalid key: {}", k));
+                }
+                self.cpu.keypad_mut().press(k as u8);
+                self.cpu.keypad_mut().release(k as u8);
+            }
+            ("screen", []) => print_display(&self.cpu),
+            ("reset", []) => {
+                let mut cpu = (self.reset)().map_err(|err| err.to_string())?;
+                if let Some(observer) = self.cpu.take_observer() {
+                    cpu.set_observer(observer);
+                }
+                for &breakpoint in self.cpu.breakpoints() {
+                    cpu.add_breakpoint(breakpoint);
+                }
+                for &watchpoint in self.cpu.watchpoints() {
+                    cpu.add_watchpoint(watchpoint);
+                }
+                self.cpu = cpu;
+                self.show_current();
+            }
+            ("h" | "help", _) => println!("{}", HELP),
+            ("q" | "quit", []) => return Ok(false),
+            _ => {
+                return Err(format!(
+                    "unknown command: {} (try 'help')",
+                    args_line(command, args)
+                ))
+            }
+        }
+        Ok(true)
+    }
+
+    /// Executes up to `limit` instructions, or until something stops it.
+    fn resume(&mut self, limit: Option<u64>) {
+        let stop = match limit {
+            Some(limit) => self.cpu.step_for(limit),
+            None => self.cpu.run(),
+        };
+        match stop {
+            Ok(StopReason::CycleLimit) => {}
+            Ok(StopReason::Breakpoint(breakpoint)) => match breakpoint.condition {
+                Some(condition) => println!("breakpoint at {:03x}: {}", breakpoint.addr, condition),
+                None => println!("breakpoint at {:03x}", breakpoint.addr),
+            },
+            Ok(StopReason::Watchpoint(hit)) => {
+                print!("watchpoint: {} by {:03x}", hit.watchpoint, hit.addr);
+                match hit.watchpoint {
+                    Watchpoint::Read(_) => println!(" (value {:02x})", hit.new),
+                    _ => println!(" ({:02x} -> {:02x})", hit.old, hit.new),
+                }
+            }
+            Ok(StopReason::Halted) => println!("halted"),
+            Ok(StopReason::WaitingForKey) => println!("waiting for a key press (use 'key <k>')"),
+            Ok(StopReason::WaitingForVblank) => println!("waiting for a timer tick"),
+            Err(err) => println!("error: {}", err),
+        }
+        self.show_current();
+    }
+
+    fn show_current(&self) {
+        let pc = self.cpu.pc();
+        let memory = self.cpu.memory();
+        let end = pc.saturating_add(4).min(memory.len());
+        match disassemble_linear(&memory[pc.min(end)..end], pc)
+            .lines()
+            .first()
+        {
+            Some(line) => println!("=> {}", line),
+            None => println!("=> {:03x}: out of bounds", pc),
+        }
+    }
+
+    fn disassemble(&self, start: usize, count: usize) -> Result<(), String> {
+        let memory = self.cpu.memory();
+        if start >= memory.len() {
+            return Err(format!("{:03x} is out of bounds", start));
+        }
+        let end = start
+            .saturating_add(count.saturating_mul(2))
+            .min(memory.len());
+        for line in disassemble_linear(&memory[start..end], start).lines() {
+            let marker = if line.addr == self.cpu.pc() {
+                "=>"
+            } else if self.cpu.breakpoints().iter().any(|b| b.addr == line.addr) {
+                " *"
+            } else {
+                "  "
+            };
+            // Print the label ourselves so the marker lines up with the
+            // instruction rather than the label.
+            if let Some(label) = &line.label {
+                println!("{}:", label);
+            }
+            let line = Line {
+                label: None,
+                ..line.clone()
+            };
+            println!("{} {}", marker, line);
+        }
+        Ok(())
+    }
+
+    fn hexdump(&self, start: usize, len: usize) -> Result<(), String> {
+        let memory = self.cpu.memory();
+        if start >= memory.len() {
+            return Err(format!("{:03x} is out of bounds", start));
+        }
+        let end = start.saturating_add(len).min(memory.len());
+        for (row, chunk) in memory[start..end].chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                .collect();
+            println!("{:03x}: {:<47}  {}", start + row * 16, hex.join(" "), ascii);
+        }
+        Ok(())
+    }
+}
+
+fn args_line(command: &str, args: &[&str]) -> String {
+    std::iter::once(command)
+        .chain(args.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn number(s: &str) -> Result<u64, String> {
+    parse_number(s).ok_or(format!("invalid number: {}", s))
+}
+
+/// Narrows a parsed number to the width of what it is stored in.
+fn fits<T: TryFrom<u64>>(value: u64, target: &str) -> Result<T, String> {
+    T::try_from(value).map_err(|_| format!("{:#x} does not fit in {}", value, target))
+}
+
+fn address(s: &str) -> Result<usize, String> {
+    parse_number(s)
+        .map(|addr| addr as usize)
+        .ok_or(format!("invalid address: {}", s))
+}
@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::instruction::{decode, Instruction};
+
+/// Longest run of data bytes put on a single `DB` line.
+const DATA_BYTES_PER_LINE: usize = 4;
+
+/// One line of a [`Listing`]: either a decoded instruction or a run of bytes
+/// that was never reached as code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// Address of the first byte.
+    pub addr: usize,
+    /// The raw bytes this line covers.
+    pub bytes: Vec<u8>,
+    /// `None` for data lines.
+    pub instruction: Option<Instruction>,
+    /// Label defined at this address, if anything branches here.
+    pub label: Option<String>,
+    pub mnemonic: &'static str,
+    /// Operands with branch targets replaced by their labels.
+    pub operands: String,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(label) = &self.label {
+            writeln!(f, "{}:", label)?;
+        }
+        let hex: String = self.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let text = format!("{:<5} {}", self.mnemonic, self.operands);
+        write!(f, "{:03x}: {:<8}  {}", self.addr, hex, text.trim_end())
+    }
+}
+
+/// A disassembled block of memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Listing {
+    lines: Vec<Line>,
+    labels: BTreeMap<usize, String>,
+}
+
+impl Listing {
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+    /// The label given to `addr`, if it is a branch destination.
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Disassembles `bytes`, loaded at `origin`, by following the control flow
+/// from `entry`.
+///
+/// Jumps, calls and skips are traced to find every reachable instruction;
+/// bytes never reached are listed as `DB` data, so sprites and tables
+/// embedded in a ROM don't show up as nonsense code. Flow that can't be
+/// followed statically (BNNN, or branches leaving the block) ends the trace.
+/// Call targets are labelled `sub_NNN` and jump targets `loc_NNN`.
+pub fn disassemble(bytes: &[u8], origin: usize, entry: usize) -> Listing {
+    let block = Block { bytes, origin };
+    let mut starts = vec![false; bytes.len()];
+    let mut covered = vec![false; bytes.len()];
+    let mut labels = BTreeMap::new();
+    let mut pending = vec![entry];
+
+    while let Some(mut addr) = pending.pop() {
+        while let Some(instruction) = block.instruction(addr) {
+            let offset = addr - origin;
+            let size = instruction.size();
+            if covered[offset..offset + size].iter().any(|&c| c) {
+                break;
+            }
+            if matches!(instruction, Instruction::Illegal(_)) {
+                break;
+            }
+            starts[offset] = true;
+            covered[offset..offset + size].fill(true);
+            let next = addr + size;
+            match instruction {
+                Instruction::Jump { nnn } => {
+                    block.add_label(&mut labels, nnn, "loc");
+                    pending.push(nnn as usize);
+                    break;
+                }
+                Instruction::Call { nnn } => {
+                    block.add_label(&mut labels, nnn, "sub");
+                    pending.push(nnn as usize);
+                }
+                Instruction::SkipEqImm { .. }
+                | Instruction::SkipNeImm { .. }
+                | Instruction::SkipEqReg { .. }
+                | Instruction::SkipNeReg { .. }
+                | Instruction::SkipKeyPressed { .. }
+                | Instruction::SkipKeyNotPressed { .. } => {
+                    let skipped = block.instruction(next).map_or(2, |i| i.size());
+                    pending.push(next + skipped);
+                }
+                Instruction::Halt
+                | Instruction::Return
+                | Instruction::Exit
+                | Instruction::JumpOffset { .. } => break,
+                _ => {}
+            }
+            addr = next;
+        }
+    }
+
+    block.listing(&starts, labels)
+}
+
+/// Disassembles `bytes`, loaded at `origin`, treating every word as an
+/// instruction. Useful for arbitrary memory ranges where the entry point is
+/// unknown; unlike [`disassemble`] it will happily decode data as code.
+pub fn disassemble_linear(bytes: &[u8], origin: usize) -> Listing {
+    let block = Block { bytes, origin };
+    let mut starts = vec![false; bytes.len()];
+    let mut labels = BTreeMap::new();
+    let mut addr = origin;
+
+    while let Some(instruction) = block.instruction(addr) {
+        starts[addr - origin] = true;
+        match instruction {
+            Instruction::Jump { nnn } => block.add_label(&mut labels, nnn, "loc"),
+            Instruction::Call { nnn } => block.add_label(&mut labels, nnn, "sub"),
+            _ => {}
+        }
+        addr += instruction.size();
+    }
+
+    block.listing(&starts, labels)
+}
+
+struct Block<'a> {
+    bytes: &'a [u8],
+    origin: usize,
+}
+
+impl Block<'_> {
+    fn contains(&self, addr: usize) -> bool {
+        addr >= self.origin && addr < self.origin + self.bytes.len()
+    }
+    fn word(&self, addr: usize) -> Option<u16> {
+        if !self.contains(addr) || !self.contains(addr + 1) {
+            return None;
+        }
+        let offset = addr - self.origin;
+        Some(u16::from_be_bytes([
+            self.bytes[offset],
+            self.bytes[offset + 1],
+        ]))
+    }
+    /// The instruction at `addr`, if all of its bytes lie inside the block.
+    fn instruction(&self, addr: usize) -> Option<Instruction> {
+        let instruction = decode(self.word(addr)?);
+        if instruction.size() == 4 && self.word(addr + 2).is_none() {
+            return None;
+        }
+        Some(instruction)
+    }
+    fn add_label(&self, labels: &mut BTreeMap<usize, String>, target: u16, prefix: &str) {
+        let target = target as usize;
+        if !self.contains(target) {
+            return;
+        }
+        let label = format!("{}_{:03x}", prefix, target);
+        // A call makes the destination a subroutine even if it is also jumped to.
+        match labels.get(&target) {
+            Some(existing) if existing.starts_with("sub") => {}
+            _ => {
+                labels.insert(target, label);
+            }
+        }
+    }
+    fn listing(&self, starts: &[bool], mut labels: BTreeMap<usize, String>) -> Listing {
+        // A branch into the middle of a decoded instruction has no line to
+        // put its label on, so it keeps its numeric operand instead.
+        let mut inside = vec![false; self.bytes.len()];
+        for offset in (0..self.bytes.len()).filter(|&offset| starts[offset]) {
+            let size = self
+                .instruction(self.origin + offset)
+                .map_or(2, |i| i.size());
+            let end = (offset + size).min(self.bytes.len());
+            inside[offset + 1..end].fill(true);
+        }
+        labels.retain(|&addr, _| !inside[addr - self.origin]);
+
+        let mut lines = Vec::new();
+        let mut offset = 0;
+
+        while offset < self.bytes.len() {
+            let addr = self.origin + offset;
+            let label = labels.get(&addr).cloned();
+            if starts[offset] {
+                let instruction = self
+                    .instruction(addr)
+                    .expect("instruction starts lie inside the block");
+                let size = instruction.size();
+                let operands = match instruction {
+                    Instruction::Jump { nnn } | Instruction::Call { nnn } => labels
+                        .get(&(nnn as usize))
+                        .cloned()
+                        .unwrap_or_else(|| instruction.operands()),
+                    Instruction::LongLoadIndex => {
+                        format!("I, LONG {:#06x}", self.word(addr + 2).unwrap_or(0))
+                    }
+                    _ => instruction.operands(),
+                };
+                lines.push(Line {
+                    addr,
+                    bytes: self.bytes[offset..offset + size].to_vec(),
+                    instruction: Some(instruction),
+                    label,
+                    mnemonic: instruction.mnemonic(),
+                    operands,
+                });
+                offset += size;
+            } else {
+                let mut end = offset + 1;
+                while end < self.bytes.len()
+                    && end - offset < DATA_BYTES_PER_LINE
+                    && !starts[end]
+                    && !labels.contains_key(&(self.origin + end))
+                {
+                    end += 1;
+                }
+                let bytes = self.bytes[offset..end].to_vec();
+                let operands = bytes
+                    .iter()
+                    .map(|b| format!("{:#04x}", b))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                lines.push(Line {
+                    addr,
+                    bytes,
+                    instruction: None,
+                    label,
+                    mnemonic: "DB",
+                    operands,
+                });
+                offset = end;
+            }
+        }
+
+        Listing { lines, labels }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(listing: &Listing) -> Vec<String> {
+        listing.to_string().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn unreached_bytes_are_listed_as_data() {
+        let rom = [
+            0x22, 0x08, // 200: CALL 208
+            0x12, 0x0A, // 202: JP 20A
+            0xF0, 0x90, 0x90, 0xF0, // 204: sprite
+            0x00, 0xEE, // 208: RET
+            0x00, 0x00, // 20A: HALT
+        ];
+        assert_eq!(
+            text(&disassemble(&rom, 0x200, 0x200)),
+            [
+                "200: 2208      CALL  sub_208",
+                "202: 120a      JP    loc_20a",
+                "204: f09090f0  DB    0xf0, 0x90, 0x90, 0xf0",
+                "sub_208:",
+                "208: 00ee      RET",
+                "loc_20a:",
+                "20a: 0000      HALT",
+            ]
+        );
+    }
+
+    #[test]
+    fn branches_into_an_instruction_keep_their_address() {
+        let rom = [0xF0, 0x00, 0x12, 0x34, 0x12, 0x02];
+        let listing = disassemble_linear(&rom, 0x200);
+        assert_eq!(listing.label(0x202), None);
+        assert_eq!(
+            text(&listing),
+            [
+                "200: f0001234  LD    I, LONG 0x1234",
+                "204: 1202      JP    0x202",
+            ]
+        );
+    }
+}
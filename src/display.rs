@@ -0,0 +1,292 @@
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+/// Resolution of the SUPER-CHIP high-resolution mode (00FF).
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+/// Number of XO-CHIP bitplanes. Plain CHIP-8 and SUPER-CHIP only use the first.
+pub const PLANE_COUNT: usize = 2;
+
+/// Framebuffer drawn to by 00E0 and DXYN.
+///
+/// The screen is made of [`PLANE_COUNT`] monochrome bitplanes. Drawing,
+/// clearing and scrolling only touch the planes selected with
+/// [`Display::select_planes`] (XO-CHIP FN01); by default that is just the
+/// first, which makes the display behave as a plain monochrome screen.
+pub struct Display {
+    width: usize,
+    height: usize,
+    planes: [Vec<bool>; PLANE_COUNT],
+    selected: u8,
+    hires: bool,
+    wrap: bool,
+    dirty: bool,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Display {
+            width: DISPLAY_WIDTH,
+            height: DISPLAY_HEIGHT,
+            planes: std::array::from_fn(|_| vec![false; DISPLAY_WIDTH * DISPLAY_HEIGHT]),
+            selected: 0b01,
+            hires: false,
+            wrap: false,
+            dirty: false,
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// Pixels of the first bitplane in row-major order; `true` means lit.
+    pub fn pixels(&self) -> &[bool] {
+        &self.planes[0]
+    }
+    /// Pixels of bitplane `plane` in row-major order.
+    pub fn plane(&self, plane: usize) -> &[bool] {
+        &self.planes[plane]
+    }
+    /// Whether the pixel is lit on any plane.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.color(x, y) != 0
+    }
+    /// The pixel's colour index: bit N is set when it is lit on plane N.
+    pub fn color(&self, x: usize, y: usize) -> u8 {
+        let i = y * self.width + x;
+        self.planes
+            .iter()
+            .enumerate()
+            .fold(0, |color, (n, plane)| color | (plane[i] as u8) << n)
+    }
+    /// Bitmask of the planes that drawing, clearing and scrolling affect.
+    pub fn selected_planes(&self) -> u8 {
+        self.selected
+    }
+    pub fn select_planes(&mut self, mask: u8) {
+        self.selected = mask & ((1 << PLANE_COUNT) - 1);
+    }
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+    /// Switches between 64x32 and 128x64. The screen is cleared either way.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        (self.width, self.height) = if hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        };
+        for plane in &mut self.planes {
+            *plane = vec![false; self.width * self.height];
+        }
+        self.dirty = true;
+    }
+    /// Whether sprites running off the right or bottom edge wrap around to
+    /// the opposite side instead of being clipped.
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+    /// Set whenever the framebuffer changes; frontends clear it with
+    /// [`Display::mark_clean`] once they have redrawn.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+    pub fn clear(&mut self) {
+        for plane in self.selected_planes_mut() {
+            plane.fill(false);
+        }
+        self.dirty = true;
+    }
+    /// XORs an 8-pixel-wide sprite onto the screen with its top-left corner
+    /// at (`x`, `y`). The starting position always wraps; pixels past the
+    /// edge wrap or clip depending on [`Display::wraps`]. Returns `true` if
+    /// any lit pixel was turned off.
+    ///
+    /// With more than one plane selected, `rows` holds one sprite per
+    /// selected plane, back to back, lowest plane first.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, rows: &[u8]) -> bool {
+        self.blit(x, y, 1, rows)
+    }
+    /// Like [`Display::draw_sprite`] for the 16x16 SUPER-CHIP sprites drawn
+    /// by DXY0, two bytes per row.
+    pub fn draw_sprite16(&mut self, x: usize, y: usize, rows: &[u8]) -> bool {
+        self.blit(x, y, 2, rows)
+    }
+    /// Moves the picture down `n` rows, blanking the rows scrolled in.
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.height) * self.width;
+        for plane in self.selected_planes_mut() {
+            plane.rotate_right(n);
+            plane[..n].fill(false);
+        }
+        self.dirty = true;
+    }
+    /// Moves the picture up `n` rows, blanking the rows scrolled in.
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.height) * self.width;
+        for plane in self.selected_planes_mut() {
+            plane.rotate_left(n);
+            let len = plane.len();
+            plane[len - n..].fill(false);
+        }
+        self.dirty = true;
+    }
+    /// Moves the picture right `n` columns, blanking the columns scrolled in.
+    pub fn scroll_right(&mut self, n: usize) {
+        let n = n.min(self.width);
+        let width = self.width;
+        for plane in self.selected_planes_mut() {
+            for row in plane.chunks_mut(width) {
+                row.rotate_right(n);
+                row[..n].fill(false);
+            }
+        }
+        self.dirty = true;
+    }
+    /// Moves the picture left `n` columns, blanking the columns scrolled in.
+    pub fn scroll_left(&mut self, n: usize) {
+        let n = n.min(self.width);
+        let width = self.width;
+        for plane in self.selected_planes_mut() {
+            for row in plane.chunks_mut(width) {
+                row.rotate_left(n);
+                row[width - n..].fill(false);
+            }
+        }
+        self.dirty = true;
+    }
+    fn selected_planes_mut(&mut self) -> impl Iterator<Item = &mut Vec<bool>> {
+        let selected = self.selected;
+        self.planes
+            .iter_mut()
+            .enumerate()
+            .filter(move |(n, _)| selected & (1 << n) != 0)
+            .map(|(_, plane)| plane)
+    }
+    fn blit(&mut self, x: usize, y: usize, bytes_per_row: usize, data: &[u8]) -> bool {
+        let (width, height, wrap) = (self.width, self.height, self.wrap);
+        let origin_x = x % width;
+        let origin_y = y % height;
+        let plane_count = self.selected.count_ones() as usize;
+        if plane_count == 0 {
+            return false;
+        }
+        let per_plane = data.len() / plane_count;
+        let mut collision = false;
+        let mut changed = false;
+
+        for (plane, sprite) in self
+            .selected_planes_mut()
+            .zip(data.chunks(per_plane.max(1)))
+        {
+            for (row, bytes) in sprite.chunks(bytes_per_row).enumerate() {
+                let mut py = origin_y + row;
+                if py >= height {
+                    if !wrap {
+                        break;
+                    }
+                    py %= height;
+                }
+                for col in 0..bytes.len() * 8 {
+                    if bytes[col / 8] & (0x80 >> (col % 8)) == 0 {
+                        continue;
+                    }
+                    let mut px = origin_x + col;
+                    if px >= width {
+                        if !wrap {
+                            break;
+                        }
+                        px %= width;
+                    }
+                    let pixel = &mut plane[py * width + px];
+                    collision |= *pixel;
+                    *pixel ^= true;
+                    changed = true;
+                }
+            }
+        }
+        self.dirty |= changed;
+        collision
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CPU;
+
+    /// Coordinates of the lit pixels, row by row.
+    fn lit(display: &Display) -> Vec<(usize, usize)> {
+        (0..display.height())
+            .flat_map(|y| (0..display.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| display.pixel(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn drawing_over_a_lit_pixel_sets_vf() {
+        let mut cpu = CPU::new();
+        cpu.load_asm(
+            "
+                  LD I, dot
+                  DRW V0, V0, 1
+                  LD V1, VF
+                  DRW V0, V0, 1
+                  HALT
+            dot:  DB 0x80
+            ",
+        )
+        .unwrap();
+        cpu.run().unwrap();
+        assert_eq!((cpu.registers()[0x1], cpu.registers()[0xF]), (0, 1));
+        assert!(lit(cpu.display()).is_empty());
+    }
+
+    #[test]
+    fn sprites_clip_or_wrap_at_the_right_and_bottom_edges() {
+        for (wrap, expected) in [
+            (false, vec![(63, 31)]),
+            (true, vec![(0, 0), (63, 0), (0, 31), (63, 31)]),
+        ] {
+            let mut display = Display::new();
+            display.set_wrap(wrap);
+            display.draw_sprite(63, 31, &[0xC0, 0xC0]);
+            assert_eq!(lit(&display), expected, "wrap: {}", wrap);
+        }
+    }
+
+    #[test]
+    fn the_starting_position_always_wraps() {
+        let mut display = Display::new();
+        display.draw_sprite(DISPLAY_WIDTH + 2, DISPLAY_HEIGHT + 3, &[0x80]);
+        assert_eq!(lit(&display), [(2, 3)]);
+    }
+
+    #[test]
+    fn changes_mark_the_display_dirty_until_it_is_marked_clean() {
+        let mut display = Display::new();
+        assert!(!display.is_dirty());
+        display.draw_sprite(0, 0, &[0x00]);
+        assert!(!display.is_dirty(), "a blank sprite changes nothing");
+        display.draw_sprite(0, 0, &[0x80]);
+        assert!(display.is_dirty());
+        display.mark_clean();
+        assert!(!display.is_dirty());
+        display.clear();
+        assert!(display.is_dirty());
+    }
+}
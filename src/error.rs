@@ -0,0 +1,95 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    StackOverflow { addr: u16 },
+    StackUnderflow { addr: u16 },
+    IllegalOpcode { addr: u16, opcode: u16 },
+    PcOutOfBounds { pc: usize },
+    MemoryFault { addr: usize },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::StackOverflow { addr } => write!(f, "stack overflow at {:03x}", addr),
+            CpuError::StackUnderflow { addr } => write!(f, "stack underflow at {:03x}", addr),
+            CpuError::IllegalOpcode { addr, opcode } => {
+                write!(f, "illegal opcode {:04x} at {:03x}", opcode, addr)
+            }
+            CpuError::PcOutOfBounds { pc } => {
+                write!(f, "program counter out of bounds: {:04x}", pc)
+            }
+            CpuError::MemoryFault { addr } => {
+                write!(f, "memory access out of bounds: {:04x}", addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    RomTooLarge { size: usize, max: usize },
+    Asm(AsmError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "failed to read ROM: {}", err),
+            LoadError::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes but at most {} bytes fit", size, max)
+            }
+            LoadError::Asm(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::RomTooLarge { .. } => None,
+            LoadError::Asm(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl From<AsmError> for LoadError {
+    fn from(err: AsmError) -> Self {
+        LoadError::Asm(err)
+    }
+}
+
+/// An assembly error, located by file and 1-based line number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// The file the line is in, or `None` for source passed as a string.
+    pub path: Option<PathBuf>,
+    /// 0 when the error isn't about a particular line, such as an unreadable file.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) if self.line == 0 => write!(f, "{}: {}", path.display(), self.message),
+            Some(path) => write!(f, "{}:{}: {}", path.display(), self.line, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
@@ -0,0 +1,412 @@
+use std::fmt;
+
+/// A decoded CHIP-8 instruction. Operand names follow the usual opcode
+/// notation: `x`/`y` are register indices, `n` a nibble, `nn` a byte and
+/// `nnn` a 12-bit address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0000 — stop execution.
+    Halt,
+    /// 00E0
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 00CN — scroll the display down N rows (SUPER-CHIP).
+    ScrollDown { n: u8 },
+    /// 00DN — scroll the display up N rows (XO-CHIP).
+    ScrollUp { n: u8 },
+    /// 00FB — scroll the display right 4 pixels (SUPER-CHIP).
+    ScrollRight,
+    /// 00FC — scroll the display left 4 pixels (SUPER-CHIP).
+    ScrollLeft,
+    /// 00FD — exit the interpreter (SUPER-CHIP).
+    Exit,
+    /// 00FE — switch to 64x32 (SUPER-CHIP).
+    LowRes,
+    /// 00FF — switch to 128x64 (SUPER-CHIP).
+    HighRes,
+    /// 0NNN — call a machine code routine; ignored.
+    Sys { nnn: u16 },
+    /// 1NNN
+    Jump { nnn: u16 },
+    /// 2NNN
+    Call { nnn: u16 },
+    /// 3XNN — skip if VX == NN.
+    SkipEqImm { x: u8, nn: u8 },
+    /// 4XNN — skip if VX != NN.
+    SkipNeImm { x: u8, nn: u8 },
+    /// 5XY0 — skip if VX == VY.
+    SkipEqReg { x: u8, y: u8 },
+    /// 5XY2 — store VX..=VY at I, without changing I (XO-CHIP).
+    SaveRange { x: u8, y: u8 },
+    /// 5XY3 — load VX..=VY from I, without changing I (XO-CHIP).
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN — VX = NN.
+    LoadImm { x: u8, nn: u8 },
+    /// 7XNN — VX += NN, no carry.
+    AddImm { x: u8, nn: u8 },
+    /// 8XY0 — VX = VY.
+    Move { x: u8, y: u8 },
+    /// 8XY1
+    Or { x: u8, y: u8 },
+    /// 8XY2
+    And { x: u8, y: u8 },
+    /// 8XY3
+    Xor { x: u8, y: u8 },
+    /// 8XY4 — VX += VY, VF = carry.
+    Add { x: u8, y: u8 },
+    /// 8XY5 — VX -= VY, VF = NOT borrow.
+    Sub { x: u8, y: u8 },
+    /// 8XY6
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7 — VX = VY - VX, VF = NOT borrow.
+    SubN { x: u8, y: u8 },
+    /// 8XYE
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0 — skip if VX != VY.
+    SkipNeReg { x: u8, y: u8 },
+    /// ANNN — I = NNN.
+    LoadIndex { nnn: u16 },
+    /// BNNN — jump to NNN + V0 (or XNN + VX, depending on quirks).
+    JumpOffset { x: u8, nnn: u16 },
+    /// CXNN — VX = random & NN.
+    Random { x: u8, nn: u8 },
+    /// DXYN — draw an N-row sprite from I at (VX, VY). On SUPER-CHIP, DXY0
+    /// draws a 16x16 sprite.
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E
+    SkipKeyPressed { x: u8 },
+    /// EXA1
+    SkipKeyNotPressed { x: u8 },
+    /// F000 NNNN — I = the 16-bit word following the opcode (XO-CHIP).
+    LongLoadIndex,
+    /// FN01 — select the bitplanes drawing affects, N a mask (XO-CHIP).
+    SelectPlanes { n: u8 },
+    /// F002 — load the 16-byte audio pattern from I (XO-CHIP).
+    LoadAudio,
+    /// FX07 — VX = delay timer.
+    LoadDelay { x: u8 },
+    /// FX0A
+    WaitKey { x: u8 },
+    /// FX15 — delay timer = VX.
+    SetDelay { x: u8 },
+    /// FX18 — sound timer = VX.
+    SetSound { x: u8 },
+    /// FX1E — I += VX.
+    AddIndex { x: u8 },
+    /// FX29 — I = address of the font glyph for VX.
+    LoadFont { x: u8 },
+    /// FX30 — I = address of the large font glyph for VX (SUPER-CHIP).
+    LoadBigFont { x: u8 },
+    /// FX3A — set the audio pattern pitch to VX (XO-CHIP).
+    SetPitch { x: u8 },
+    /// FX33
+    StoreBcd { x: u8 },
+    /// FX55 — store V0..=VX at I.
+    StoreRegisters { x: u8 },
+    /// FX65 — load V0..=VX from I.
+    LoadRegisters { x: u8 },
+    /// FX75 — save V0..=VX to the RPL user flags (SUPER-CHIP).
+    SaveFlags { x: u8 },
+    /// FX85 — load V0..=VX from the RPL user flags (SUPER-CHIP).
+    LoadFlags { x: u8 },
+    /// Anything that doesn't decode to a known instruction.
+    Illegal(u16),
+}
+
+impl Instruction {
+    /// Whether this instruction is part of the SUPER-CHIP extension rather
+    /// than the original CHIP-8 set.
+    pub fn is_schip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::ScrollDown { .. }
+                | Instruction::ScrollRight
+                | Instruction::ScrollLeft
+                | Instruction::Exit
+                | Instruction::LowRes
+                | Instruction::HighRes
+                | Instruction::LoadBigFont { .. }
+                | Instruction::SaveFlags { .. }
+                | Instruction::LoadFlags { .. }
+        )
+    }
+    /// Whether this instruction is part of the XO-CHIP extension.
+    pub fn is_xo_chip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::ScrollUp { .. }
+                | Instruction::SaveRange { .. }
+                | Instruction::LoadRange { .. }
+                | Instruction::LongLoadIndex
+                | Instruction::SelectPlanes { .. }
+                | Instruction::LoadAudio
+                | Instruction::SetPitch { .. }
+        )
+    }
+    /// Number of bytes the instruction occupies: 4 for F000 NNNN, which
+    /// carries its address in the following word, and 2 for everything else.
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LongLoadIndex => 4,
+            _ => 2,
+        }
+    }
+    /// The opcode this instruction decodes from; the inverse of [`decode`].
+    ///
+    /// For F000 NNNN only the first word is returned.
+    pub fn encode(&self) -> u16 {
+        let xy = |c: u16, x: u8, y: u8, d: u16| c << 12 | (x as u16) << 8 | (y as u16) << 4 | d;
+        let xnn = |c: u16, x: u8, nn: u8| c << 12 | (x as u16) << 8 | nn as u16;
+        let fx = |x: u8, nn: u8| xnn(0xF, x, nn);
+        match *self {
+            Instruction::Halt => 0x0000,
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollDown { n } => 0x00C0 | n as u16,
+            Instruction::ScrollUp { n } => 0x00D0 | n as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::Sys { nnn } => nnn,
+            Instruction::Jump { nnn } => 0x1000 | nnn,
+            Instruction::Call { nnn } => 0x2000 | nnn,
+            Instruction::SkipEqImm { x, nn } => xnn(0x3, x, nn),
+            Instruction::SkipNeImm { x, nn } => xnn(0x4, x, nn),
+            Instruction::SkipEqReg { x, y } => xy(0x5, x, y, 0),
+            Instruction::SaveRange { x, y } => xy(0x5, x, y, 2),
+            Instruction::LoadRange { x, y } => xy(0x5, x, y, 3),
+            Instruction::LoadImm { x, nn } => xnn(0x6, x, nn),
+            Instruction::AddImm { x, nn } => xnn(0x7, x, nn),
+            Instruction::Move { x, y } => xy(0x8, x, y, 0x0),
+            Instruction::Or { x, y } => xy(0x8, x, y, 0x1),
+            Instruction::And { x, y } => xy(0x8, x, y, 0x2),
+            Instruction::Xor { x, y } => xy(0x8, x, y, 0x3),
+            Instruction::Add { x, y } => xy(0x8, x, y, 0x4),
+            Instruction::Sub { x, y } => xy(0x8, x, y, 0x5),
+            Instruction::ShiftRight { x, y } => xy(0x8, x, y, 0x6),
+            Instruction::SubN { x, y } => xy(0x8, x, y, 0x7),
+            Instruction::ShiftLeft { x, y } => xy(0x8, x, y, 0xE),
+            Instruction::SkipNeReg { x, y } => xy(0x9, x, y, 0),
+            Instruction::LoadIndex { nnn } => 0xA000 | nnn,
+            Instruction::JumpOffset { nnn, .. } => 0xB000 | nnn,
+            Instruction::Random { x, nn } => xnn(0xC, x, nn),
+            Instruction::Draw { x, y, n } => xy(0xD, x, y, n as u16),
+            Instruction::SkipKeyPressed { x } => xnn(0xE, x, 0x9E),
+            Instruction::SkipKeyNotPressed { x } => xnn(0xE, x, 0xA1),
+            Instruction::LongLoadIndex => 0xF000,
+            Instruction::SelectPlanes { n } => fx(n, 0x01),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::LoadDelay { x } => fx(x, 0x07),
+            Instruction::WaitKey { x } => fx(x, 0x0A),
+            Instruction::SetDelay { x } => fx(x, 0x15),
+            Instruction::SetSound { x } => fx(x, 0x18),
+            Instruction::AddIndex { x } => fx(x, 0x1E),
+            Instruction::LoadFont { x } => fx(x, 0x29),
+            Instruction::LoadBigFont { x } => fx(x, 0x30),
+            Instruction::SetPitch { x } => fx(x, 0x3A),
+            Instruction::StoreBcd { x } => fx(x, 0x33),
+            Instruction::StoreRegisters { x } => fx(x, 0x55),
+            Instruction::LoadRegisters { x } => fx(x, 0x65),
+            Instruction::SaveFlags { x } => fx(x, 0x75),
+            Instruction::LoadFlags { x } => fx(x, 0x85),
+            Instruction::Illegal(opcode) => opcode,
+        }
+    }
+    /// The assembler mnemonic, following Cowgod's notation with SUPER-CHIP
+    /// and XO-CHIP additions. Illegal opcodes are shown as a `DW` data word.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Halt => "HALT",
+            Instruction::ClearScreen => "CLS",
+            Instruction::Return => "RET",
+            Instruction::ScrollDown { .. } => "SCD",
+            Instruction::ScrollUp { .. } => "SCU",
+            Instruction::ScrollRight => "SCR",
+            Instruction::ScrollLeft => "SCL",
+            Instruction::Exit => "EXIT",
+            Instruction::LowRes => "LOW",
+            Instruction::HighRes => "HIGH",
+            Instruction::Sys { .. } => "SYS",
+            Instruction::Jump { .. } | Instruction::JumpOffset { .. } => "JP",
+            Instruction::Call { .. } => "CALL",
+            Instruction::SkipEqImm { .. } | Instruction::SkipEqReg { .. } => "SE",
+            Instruction::SkipNeImm { .. } | Instruction::SkipNeReg { .. } => "SNE",
+            Instruction::SaveRange { .. } => "SAVE",
+            Instruction::LoadRange { .. } => "LOAD",
+            Instruction::AddImm { .. } | Instruction::Add { .. } | Instruction::AddIndex { .. } => {
+                "ADD"
+            }
+            Instruction::Or { .. } => "OR",
+            Instruction::And { .. } => "AND",
+            Instruction::Xor { .. } => "XOR",
+            Instruction::Sub { .. } => "SUB",
+            Instruction::ShiftRight { .. } => "SHR",
+            Instruction::SubN { .. } => "SUBN",
+            Instruction::ShiftLeft { .. } => "SHL",
+            Instruction::Random { .. } => "RND",
+            Instruction::Draw { .. } => "DRW",
+            Instruction::SkipKeyPressed { .. } => "SKP",
+            Instruction::SkipKeyNotPressed { .. } => "SKNP",
+            Instruction::SelectPlanes { .. } => "PLANE",
+            Instruction::LoadAudio => "AUDIO",
+            Instruction::SetPitch { .. } => "PITCH",
+            Instruction::LoadImm { .. }
+            | Instruction::Move { .. }
+            | Instruction::LoadIndex { .. }
+            | Instruction::LongLoadIndex
+            | Instruction::LoadDelay { .. }
+            | Instruction::WaitKey { .. }
+            | Instruction::SetDelay { .. }
+            | Instruction::SetSound { .. }
+            | Instruction::LoadFont { .. }
+            | Instruction::LoadBigFont { .. }
+            | Instruction::StoreBcd { .. }
+            | Instruction::StoreRegisters { .. }
+            | Instruction::LoadRegisters { .. }
+            | Instruction::SaveFlags { .. }
+            | Instruction::LoadFlags { .. } => "LD",
+            Instruction::Illegal(_) => "DW",
+        }
+    }
+    /// The operands as they appear after [`Instruction::mnemonic`], e.g.
+    /// `V0, 0x05` or `0x2a0`. Empty for instructions that take none.
+    ///
+    /// The address word of F000 NNNN is not part of the decoded instruction,
+    /// so it is shown as just `I, LONG`.
+    pub fn operands(&self) -> String {
+        match *self {
+            Instruction::Halt
+            | Instruction::ClearScreen
+            | Instruction::Return
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::LowRes
+            | Instruction::HighRes
+            | Instruction::LoadAudio => String::new(),
+            Instruction::ScrollDown { n }
+            | Instruction::ScrollUp { n }
+            | Instruction::SelectPlanes { n } => n.to_string(),
+            Instruction::Sys { nnn } | Instruction::Jump { nnn } | Instruction::Call { nnn } => {
+                format!("{:#05x}", nnn)
+            }
+            Instruction::SkipEqImm { x, nn }
+            | Instruction::SkipNeImm { x, nn }
+            | Instruction::LoadImm { x, nn }
+            | Instruction::AddImm { x, nn }
+            | Instruction::Random { x, nn } => format!("V{:X}, {:#04x}", x, nn),
+            Instruction::SkipEqReg { x, y }
+            | Instruction::SkipNeReg { x, y }
+            | Instruction::SaveRange { x, y }
+            | Instruction::LoadRange { x, y }
+            | Instruction::Move { x, y }
+            | Instruction::Or { x, y }
+            | Instruction::And { x, y }
+            | Instruction::Xor { x, y }
+            | Instruction::Add { x, y }
+            | Instruction::Sub { x, y }
+            | Instruction::ShiftRight { x, y }
+            | Instruction::SubN { x, y }
+            | Instruction::ShiftLeft { x, y } => format!("V{:X}, V{:X}", x, y),
+            Instruction::LoadIndex { nnn } => format!("I, {:#05x}", nnn),
+            Instruction::JumpOffset { nnn, .. } => format!("V0, {:#05x}", nnn),
+            Instruction::Draw { x, y, n } => format!("V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKeyPressed { x }
+            | Instruction::SkipKeyNotPressed { x }
+            | Instruction::SetPitch { x } => format!("V{:X}", x),
+            Instruction::LongLoadIndex => "I, LONG".to_string(),
+            Instruction::LoadDelay { x } => format!("V{:X}, DT", x),
+            Instruction::WaitKey { x } => format!("V{:X}, K", x),
+            Instruction::SetDelay { x } => format!("DT, V{:X}", x),
+            Instruction::SetSound { x } => format!("ST, V{:X}", x),
+            Instruction::AddIndex { x } => format!("I, V{:X}", x),
+            Instruction::LoadFont { x } => format!("F, V{:X}", x),
+            Instruction::LoadBigFont { x } => format!("HF, V{:X}", x),
+            Instruction::StoreBcd { x } => format!("B, V{:X}", x),
+            Instruction::StoreRegisters { x } => format!("[I], V{:X}", x),
+            Instruction::LoadRegisters { x } => format!("V{:X}, [I]", x),
+            Instruction::SaveFlags { x } => format!("R, V{:X}", x),
+            Instruction::LoadFlags { x } => format!("V{:X}, R", x),
+            Instruction::Illegal(opcode) => format!("{:#06x}", opcode),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operands = self.operands();
+        if operands.is_empty() {
+            write!(f, "{}", self.mnemonic())
+        } else {
+            write!(f, "{} {}", self.mnemonic(), operands)
+        }
+    }
+}
+
+pub fn decode(opcode: u16) -> Instruction {
+    let c = ((opcode & 0xF000) >> 12) as u8;
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let d = (opcode & 0x000F) as u8;
+
+    let nnn = opcode & 0x0FFF;
+    let nn = (opcode & 0x00FF) as u8;
+    match (c, x, y, d) {
+        (0, 0, 0, 0) => Instruction::Halt,
+        (0, 0, 0xE, 0) => Instruction::ClearScreen,
+        (0, 0, 0xE, 0xE) => Instruction::Return,
+        (0, 0, 0xC, _) => Instruction::ScrollDown { n: d },
+        (0, 0, 0xD, _) => Instruction::ScrollUp { n: d },
+        (0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+        (0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+        (0, 0, 0xF, 0xD) => Instruction::Exit,
+        (0, 0, 0xF, 0xE) => Instruction::LowRes,
+        (0, 0, 0xF, 0xF) => Instruction::HighRes,
+        (0, _, _, _) => Instruction::Sys { nnn },
+        (0x1, _, _, _) => Instruction::Jump { nnn },
+        (0x2, _, _, _) => Instruction::Call { nnn },
+        (0x3, _, _, _) => Instruction::SkipEqImm { x, nn },
+        (0x4, _, _, _) => Instruction::SkipNeImm { x, nn },
+        (0x5, _, _, 0) => Instruction::SkipEqReg { x, y },
+        (0x5, _, _, 2) => Instruction::SaveRange { x, y },
+        (0x5, _, _, 3) => Instruction::LoadRange { x, y },
+        (0x6, _, _, _) => Instruction::LoadImm { x, nn },
+        (0x7, _, _, _) => Instruction::AddImm { x, nn },
+        (0x8, _, _, 0x0) => Instruction::Move { x, y },
+        (0x8, _, _, 0x1) => Instruction::Or { x, y },
+        (0x8, _, _, 0x2) => Instruction::And { x, y },
+        (0x8, _, _, 0x3) => Instruction::Xor { x, y },
+        (0x8, _, _, 0x4) => Instruction::Add { x, y },
+        (0x8, _, _, 0x5) => Instruction::Sub { x, y },
+        (0x8, _, _, 0x6) => Instruction::ShiftRight { x, y },
+        (0x8, _, _, 0x7) => Instruction::SubN { x, y },
+        (0x8, _, _, 0xE) => Instruction::ShiftLeft { x, y },
+        (0x9, _, _, 0) => Instruction::SkipNeReg { x, y },
+        (0xA, _, _, _) => Instruction::LoadIndex { nnn },
+        (0xB, _, _, _) => Instruction::JumpOffset { x, nnn },
+        (0xC, _, _, _) => Instruction::Random { x, nn },
+        (0xD, _, _, _) => Instruction::Draw { x, y, n: d },
+        (0xE, _, 0x9, 0xE) => Instruction::SkipKeyPressed { x },
+        (0xE, _, 0xA, 0x1) => Instruction::SkipKeyNotPressed { x },
+        (0xF, 0, 0x0, 0x0) => Instruction::LongLoadIndex,
+        (0xF, _, 0x0, 0x1) => Instruction::SelectPlanes { n: x },
+        (0xF, 0, 0x0, 0x2) => Instruction::LoadAudio,
+        (0xF, _, 0x0, 0x7) => Instruction::LoadDelay { x },
+        (0xF, _, 0x0, 0xA) => Instruction::WaitKey { x },
+        (0xF, _, 0x1, 0x5) => Instruction::SetDelay { x },
+        (0xF, _, 0x1, 0x8) => Instruction::SetSound { x },
+        (0xF, _, 0x1, 0xE) => Instruction::AddIndex { x },
+        (0xF, _, 0x2, 0x9) => Instruction::LoadFont { x },
+        (0xF, _, 0x3, 0x0) => Instruction::LoadBigFont { x },
+        (0xF, _, 0x3, 0xA) => Instruction::SetPitch { x },
+        (0xF, _, 0x3, 0x3) => Instruction::StoreBcd { x },
+        (0xF, _, 0x5, 0x5) => Instruction::StoreRegisters { x },
+        (0xF, _, 0x6, 0x5) => Instruction::LoadRegisters { x },
+        (0xF, _, 0x7, 0x5) => Instruction::SaveFlags { x },
+        (0xF, _, 0x8, 0x5) => Instruction::LoadFlags { x },
+        _ => Instruction::Illegal(opcode),
+    }
+}
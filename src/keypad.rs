@@ -0,0 +1,79 @@
+/// State of the 16-key hexadecimal keypad (keys 0x0–0xF).
+///
+/// The host presses and releases keys; the CPU reads them for EX9E/EXA1 and
+/// waits on them for FX0A.
+#[derive(Debug, Default, Clone)]
+pub struct Keypad {
+    keys: [bool; 16],
+    waiting: bool,
+    released: Option<u8>,
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Only the low nibble of `key` is used.
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+    pub fn press(&mut self, key: u8) {
+        self.keys[(key & 0xF) as usize] = true;
+    }
+    /// Releasing a key that was down completes a pending FX0A.
+    pub fn release(&mut self, key: u8) {
+        let key = key & 0xF;
+        if self.keys[key as usize] && self.waiting && self.released.is_none() {
+            self.released = Some(key);
+        }
+        self.keys[key as usize] = false;
+    }
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        if pressed {
+            self.press(key);
+        } else {
+            self.release(key);
+        }
+    }
+    pub fn release_all(&mut self) {
+        for key in 0..16 {
+            self.release(key);
+        }
+    }
+    /// Whether an FX0A is blocked waiting for a key release.
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+    /// Called by FX0A each time it executes. Returns the released key once a
+    /// full press-and-release has happened since the wait began.
+    pub(crate) fn poll_release(&mut self) -> Option<u8> {
+        if !self.waiting {
+            self.waiting = true;
+            self.released = None;
+            return None;
+        }
+        let key = self.released.take()?;
+        self.waiting = false;
+        Some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{StopReason, CPU};
+
+    #[test]
+    fn fx0a_stores_the_key_once_it_is_released() {
+        let mut cpu = CPU::new();
+        cpu.load_asm("LD V3, K\nHALT").unwrap();
+        assert_eq!(cpu.run().unwrap(), StopReason::WaitingForKey);
+
+        cpu.keypad_mut().press(0x5);
+        assert_eq!(cpu.run().unwrap(), StopReason::WaitingForKey);
+        assert_eq!(cpu.registers()[0x3], 0);
+
+        cpu.keypad_mut().release(0x5);
+        assert_eq!(cpu.run().unwrap(), StopReason::Halted);
+        assert_eq!(cpu.registers()[0x3], 0x5);
+    }
+}
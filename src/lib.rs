@@ -0,0 +1,34 @@
+mod asm;
+mod breakpoint;
+mod cpu;
+mod disasm;
+mod display;
+mod error;
+mod font;
+mod instruction;
+mod keypad;
+mod observer;
+mod platform;
+mod quirks;
+mod rng;
+mod trace;
+
+pub use asm::{assemble, assemble_file, parse_number, parse_register};
+pub use breakpoint::{
+    Breakpoint, Comparison, Condition, Operand, StopReason, WatchHit, Watchpoint,
+};
+pub use cpu::{AddressOverflow, CpuConfig, StepResult, CPU, MEMORY_4K, MEMORY_64K, TIMER_HZ};
+pub use disasm::{disassemble, disassemble_linear, Line, Listing};
+pub use display::{Display, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH, PLANE_COUNT};
+pub use error::{AsmError, CpuError, LoadError};
+pub use font::{BIG_FONT, BIG_FONT_ADDR, BIG_FONT_GLYPH_SIZE, FONT, FONT_ADDR, FONT_GLYPH_SIZE};
+pub use instruction::{decode, Instruction};
+pub use keypad::Keypad;
+pub use observer::Observer;
+pub use platform::Platform;
+pub use quirks::Quirks;
+pub use rng::{RandomSource, XorShiftRng};
+pub use trace::Tracer;
+
+/// Address where CHIP-8 programs are loaded; everything below is reserved for the interpreter.
+pub const PROGRAM_START: usize = 0x200;
@@ -1,112 +1,429 @@
-struct CPU {
-    registers: [u8; 16],
-    position_in_memory: usize,
-    memory: [u8; 0x1000],
-    stack: [u16; 16],
-    stack_pointer: usize,
+mod debugger;
+
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use cpu_emulator::{
+    assemble_file, disassemble, disassemble_linear, parse_number, AddressOverflow, CpuConfig,
+    LoadError, Platform, Quirks, StopReason, Tracer, XorShiftRng, CPU, MEMORY_64K, PROGRAM_START,
+};
+
+const USAGE: &str = "\
+usage: cpu_emulator [options] <rom>
+       cpu_emulator disasm [--start <addr>] [--linear] <rom>
+       cpu_emulator asm [-o <output>] <source>
+
+A <rom> ending in .asm is assembled before it is loaded.
+
+options:
+  --cycles <n>       stop after executing n instructions
+  --ips <n>          instructions per second used to pace the 60 Hz timers (default: 700)
+  --seed <n>         seed for CXNN random numbers (default: time-based)
+  --platform <name>  instruction set: chip8, schip, xo-chip (default: chip8)
+  --quirks <name>    quirk profile: vip, modern, schip-legacy, schip, xo-chip
+                     (default: the platform's usual profile)
+  --memory <bytes>   memory size (default: 4096; xo-chip needs the full 65536)
+  --wrap-addresses   wrap PC and I at the end of memory instead of faulting
+  --start <addr>     address to begin execution at (default: 0x200)
+  --trace            print each instruction to stdout as it executes
+  --trace-file <path>
+                     write the trace to a file instead of stdout
+  --trace-addresses <start>-<end>
+                     only trace instructions fetched from this address range
+  --trace-cycles <first>-<last>
+                     only trace this window of executed instructions, counted from 1
+  --debug            start an interactive debugger instead of running (type 'help' there)
+  --headless         do not print the display when execution stops
+  --dump-registers   print V0-VF, I, PC, SP and timers when execution stops
+  -h, --help         show this message
+
+disasm options:
+  --start <addr>     entry point to trace code from (default: 0x200)
+  --linear           decode every word as an instruction instead of following
+                     jumps and calls to tell code from data
+
+asm options:
+  -o <output>        where to write the ROM (default: <source> with a .ch8 extension)";
+
+/// Roughly the speed most modern interpreters default to.
+const DEFAULT_IPS: u32 = 700;
+
+struct Options {
+    rom: String,
+    cycles: Option<u64>,
+    ips: u32,
+    seed: Option<u64>,
+    platform: Platform,
+    quirks: Option<Quirks>,
+    memory: Option<usize>,
+    wrap_addresses: bool,
+    start: usize,
+    trace: bool,
+    trace_file: Option<String>,
+    trace_addresses: Option<RangeInclusive<usize>>,
+    trace_cycles: Option<RangeInclusive<u64>>,
+    debug: bool,
+    headless: bool,
+    dump_registers: bool,
 }
-impl CPU {
-    fn read_opcode(&self) -> u16 {
-        let p = self.position_in_memory;
-        let op_byte1 = self.memory[p] as u16;
-        let op_byte2 = self.memory[p + 1] as u16;
-        op_byte1 << 8 | op_byte2
-    }
-    fn run(&mut self) {
-        loop {
-            let opcode = self.read_opcode();
-            self.position_in_memory += 2;
-            let c = ((opcode & 0xF000) >> 12) as u8;
-            let x = ((opcode & 0x0F00) >> 8) as u8;
-            let y = ((opcode & 0x00F0) >> 4) as u8;
-            let d = ((opcode & 0x000F) >> 0) as u8;
-
-            let nnn = opcode & 0x0FFF;
-            match (c, x, y, d) {
-                (0, 0, 0, 0) => return,
-                (0, 0, 0xE, 0xE) => self.ret(),
-                (0x1, _, _, _) => self.jump(nnn),
-                (0x2, _, _, _) => self.call(nnn),
-                (0x8, _, _, 0x4) => self.add_xy(x, y),
-                (0x8, _, _, 0x5) => self.sub_xy(x, y),
-                _ => todo!("opcode {:04x}", opcode),
+
+/// Parses an inclusive range written `start-end`.
+fn parse_range(s: &str) -> Option<(u64, u64)> {
+    let (start, end) = s.split_once('-')?;
+    let (start, end) = (parse_number(start)?, parse_number(end)?);
+    (start <= end).then_some((start, end))
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut rom = None;
+    let mut cycles = None;
+    let mut ips = DEFAULT_IPS;
+    let mut seed = None;
+    let mut platform = Platform::default();
+    let mut quirks = None;
+    let mut memory = None;
+    let mut wrap_addresses = false;
+    let mut start = PROGRAM_START;
+    let mut trace = false;
+    let mut trace_file = None;
+    let mut trace_addresses = None;
+    let mut trace_cycles = None;
+    let mut debug = false;
+    let mut headless = false;
+    let mut dump_registers = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--cycles" => {
+                let v = value("--cycles")?;
+                cycles = Some(parse_number(&v).ok_or(format!("invalid cycle count: {}", v))?);
+            }
+            "--ips" => {
+                let v = value("--ips")?;
+                ips = parse_number(&v)
+                    .and_then(|n| u32::try_from(n).ok())
+                    .filter(|&n| n > 0)
+                    .ok_or(format!("invalid instructions per second: {}", v))?;
+            }
+            "--seed" => {
+                let v = value("--seed")?;
+                seed = Some(parse_number(&v).ok_or(format!("invalid seed: {}", v))?);
+            }
+            "--platform" => {
+                let v = value("--platform")?;
+                platform = Platform::from_name(&v).ok_or(format!("unknown platform: {}", v))?;
+            }
+            "--quirks" => {
+                let v = value("--quirks")?;
+                quirks =
+                    Some(Quirks::from_name(&v).ok_or(format!("unknown quirk profile: {}", v))?);
+            }
+            "--memory" => {
+                let v = value("--memory")?;
+                memory = Some(
+                    parse_number(&v)
+                        .map(|n| n as usize)
+                        .filter(|n| (PROGRAM_START..=MEMORY_64K).contains(n))
+                        .ok_or(format!("invalid memory size: {}", v))?,
+                );
             }
+            "--wrap-addresses" => wrap_addresses = true,
+            "--start" => {
+                let v = value("--start")?;
+                start = parse_number(&v).ok_or(format!("invalid start address: {}", v))? as usize;
+            }
+            "--trace" => trace = true,
+            "--trace-file" => {
+                trace_file = Some(value("--trace-file")?);
+                trace = true;
+            }
+            "--trace-addresses" => {
+                let v = value("--trace-addresses")?;
+                let (start, end) =
+                    parse_range(&v).ok_or(format!("invalid address range: {}", v))?;
+                trace_addresses = Some(start as usize..=end as usize);
+                trace = true;
+            }
+            "--trace-cycles" => {
+                let v = value("--trace-cycles")?;
+                let (first, last) =
+                    parse_range(&v).ok_or(format!("invalid cycle window: {}", v))?;
+                trace_cycles = Some(first..=last);
+                trace = true;
+            }
+            "--debug" => debug = true,
+            "--headless" => headless = true,
+            "--dump-registers" => dump_registers = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
         }
     }
-    fn call(&mut self, addr: u16) {
-        let sp = self.stack_pointer;
-        let stack = &mut self.stack;
-        if sp > stack.len() {
-            panic!("Stack overflow!")
-        }
-        stack[sp] = self.position_in_memory as u16;
-        self.stack_pointer += 1;
-        self.position_in_memory = addr as usize;
+
+    if let Some(size) = memory.filter(|&size| size < platform.memory_size()) {
+        return Err(format!(
+            "--memory {} is smaller than the {} bytes the platform needs",
+            size,
+            platform.memory_size()
+        ));
+    }
+    let memory_size = memory.unwrap_or_else(|| platform.memory_size());
+    if start >= memory_size {
+        return Err(format!(
+            "start address {:#x} is outside memory ({} bytes)",
+            start, memory_size
+        ));
     }
-    fn ret(&mut self) {
-        if self.stack_pointer == 0 {
-            panic!("Stack underflow")
+
+    Ok(Options {
+        rom: rom.ok_or("no ROM given")?,
+        cycles,
+        ips,
+        seed,
+        platform,
+        quirks,
+        memory,
+        wrap_addresses,
+        start,
+        trace,
+        trace_file,
+        trace_addresses,
+        trace_cycles,
+        debug,
+        headless,
+        dump_registers,
+    })
+}
+
+struct DisasmOptions {
+    rom: String,
+    start: usize,
+    linear: bool,
+}
+
+fn parse_disasm_args<I: Iterator<Item = String>>(mut args: I) -> Result<DisasmOptions, String> {
+    let mut rom = None;
+    let mut start = PROGRAM_START;
+    let mut linear = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--start" => {
+                let v = args.next().ok_or("--start needs a value")?;
+                start = parse_number(&v).ok_or(format!("invalid start address: {}", v))? as usize;
+            }
+            "--linear" => linear = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
         }
-        self.stack_pointer -= 1;
-        let call_addr = self.stack[self.stack_pointer];
-        self.position_in_memory = call_addr as usize;
     }
-    fn jump(&mut self, addr: u16) {
-        self.position_in_memory = addr as usize;
+
+    Ok(DisasmOptions {
+        rom: rom.ok_or("no ROM given")?,
+        start,
+        linear,
+    })
+}
+
+fn exit_with_usage(msg: String) -> ! {
+    if msg.is_empty() {
+        println!("{}", USAGE);
+        std::process::exit(0);
+    }
+    eprintln!("error: {}\n\n{}", msg, USAGE);
+    std::process::exit(2);
+}
+
+fn disasm_main<I: Iterator<Item = String>>(args: I) {
+    let options = parse_disasm_args(args).unwrap_or_else(|msg| exit_with_usage(msg));
+    let rom = match std::fs::read(&options.rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("error: failed to read ROM: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let listing = if options.linear {
+        disassemble_linear(&rom, PROGRAM_START)
+    } else {
+        disassemble(&rom, PROGRAM_START, options.start)
+    };
+    print!("{}", listing);
+}
+
+struct AsmOptions {
+    source: String,
+    output: Option<String>,
+}
+
+fn parse_asm_args<I: Iterator<Item = String>>(mut args: I) -> Result<AsmOptions, String> {
+    let mut source = None;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(args.next().ok_or("-o needs a value")?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ if source.is_none() => source = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
     }
-    fn add_xy(&mut self, x: u8, y: u8) {
-        let arg1 = self.registers[x as usize];
-        let arg2 = self.registers[y as usize];
 
-        let (val, overflow) = arg1.overflowing_add(arg2);
+    Ok(AsmOptions {
+        source: source.ok_or("no source file given")?,
+        output,
+    })
+}
 
-        println!("{} + {} = {}", arg1, arg2, val);
-        self.registers[x as usize] = val;
-        if overflow {
-            self.registers[0xF] = 1;
-        } else {
-            self.registers[0xF] = 0;
+fn asm_main<I: Iterator<Item = String>>(args: I) {
+    let options = parse_asm_args(args).unwrap_or_else(|msg| exit_with_usage(msg));
+    let output = options.output.unwrap_or_else(|| {
+        Path::new(&options.source)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+    let rom = match assemble_file(&options.source) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
         }
+    };
+    if let Err(err) = std::fs::write(&output, &rom) {
+        eprintln!("error: failed to write {}: {}", output, err);
+        std::process::exit(1);
+    }
+}
+
+fn print_display(cpu: &CPU) {
+    let display = cpu.display();
+    for y in 0..display.height() {
+        let line: String = (0..display.width())
+            .map(|x| [' ', '█', '▒', '▓'][display.color(x, y) as usize])
+            .collect();
+        println!("{}", line.trim_end());
+    }
+}
+
+fn print_registers(cpu: &CPU) {
+    for (i, chunk) in cpu.registers().chunks(8).enumerate() {
+        let regs: Vec<String> = chunk
+            .iter()
+            .enumerate()
+            .map(|(j, v)| format!("V{:X}={:02x}", i * 8 + j, v))
+            .collect();
+        println!("{}", regs.join(" "));
     }
-    fn sub_xy(&mut self, x: u8, y: u8) {
-        let arg1 = self.registers[x as usize];
-        let arg2 = self.registers[y as usize];
+    println!(
+        "I={:03x} PC={:03x} SP={} DT={:02x} ST={:02x}",
+        cpu.index(),
+        cpu.pc(),
+        cpu.stack_pointer(),
+        cpu.delay_timer(),
+        cpu.sound_timer()
+    );
+}
 
-        self.registers[0xF] = if arg1 >= arg2 { 1 } else { 0 };
-        let val = arg2.wrapping_sub(arg1);
-        println!("{} - {} = {}", arg2, arg1, val);
-        self.registers[y as usize] = val;
+/// Sets up a CPU as `options` describe, with the ROM loaded.
+fn build_cpu(options: &Options) -> Result<CPU, LoadError> {
+    let mut cpu = CPU::with_config(CpuConfig {
+        memory_size: options
+            .memory
+            .unwrap_or_else(|| options.platform.memory_size()),
+        address_overflow: if options.wrap_addresses {
+            AddressOverflow::Wrap
+        } else {
+            AddressOverflow::Fault
+        },
+        ..CpuConfig::default()
+    });
+    cpu.set_platform(options.platform);
+    cpu.set_quirks(
+        options
+            .quirks
+            .unwrap_or_else(|| options.platform.default_quirks()),
+    );
+    cpu.set_instructions_per_second(Some(options.ips));
+    match options.seed {
+        Some(seed) => cpu.seed_rng(seed),
+        None => cpu.set_rng(Box::new(XorShiftRng::from_time())),
     }
+    let loaded = if Path::new(&options.rom).extension() == Some("asm".as_ref()) {
+        cpu.load_asm_file(&options.rom)
+    } else {
+        cpu.load_rom_file(&options.rom)
+    };
+    loaded?;
+    cpu.set_pc(options.start);
+    Ok(cpu)
 }
+
 fn main() {
-    let mut cpu = CPU {
-        registers: [0; 16],
-        memory: [0; 4096],
-        position_in_memory: 0,
-        stack: [0; 16],
-        stack_pointer: 0,
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("disasm") => {
+            args.next();
+            return disasm_main(args);
+        }
+        Some("asm") => {
+            args.next();
+            return asm_main(args);
+        }
+        _ => {}
+    }
+    let options = parse_args(args).unwrap_or_else(|msg| exit_with_usage(msg));
+
+    let mut cpu = build_cpu(&options).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    });
+    if options.trace {
+        let tracer = match &options.trace_file {
+            Some(path) => Tracer::create(path).unwrap_or_else(|err| {
+                eprintln!("error: failed to create {}: {}", path, err);
+                std::process::exit(1);
+            }),
+            None => Tracer::stdout(),
+        };
+        let tracer = match options.trace_addresses.clone() {
+            Some(range) => tracer.with_addresses(range),
+            None => tracer,
+        };
+        let tracer = match options.trace_cycles.clone() {
+            Some(range) => tracer.with_cycles(range),
+            None => tracer,
+        };
+        cpu.set_observer(Box::new(tracer));
+    }
+    if options.debug {
+        debugger::run(cpu, || build_cpu(&options));
+        return;
+    }
+
+    let result = match options.cycles {
+        Some(limit) => cpu.run_for(limit),
+        None => cpu.run(),
     };
-    cpu.registers[0] = 5;
-    cpu.registers[1] = 10;
-
-    let mem = &mut cpu.memory;
-    mem[0x000] = 0x21;
-    mem[0x001] = 0x00;
-    mem[0x002] = 0x22;
-    mem[0x003] = 0x00;
-    mem[0x004] = 0x00;
-    mem[0x005] = 0x00;
-
-    mem[0x100] = 0x80;
-    mem[0x101] = 0x14;
-    mem[0x102] = 0x00;
-    mem[0x103] = 0xEE;
-
-    mem[0x200] = 0x81;
-    mem[0x201] = 0x05;
-    mem[0x202] = 0x00;
-    mem[0x203] = 0xEE;
-
-    cpu.run();
-    println!("{}", cpu.registers[0]);
+    // Dropping the tracer flushes a trace file before we might exit.
+    drop(cpu.take_observer());
+    if let Ok(StopReason::WaitingForKey) = result {
+        eprintln!("stopped waiting for a key press at {:03x}", cpu.pc());
+    }
+
+    if !options.headless {
+        print_display(&cpu);
+    }
+    if options.dump_registers {
+        print_registers(&cpu);
+    }
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,16 @@
+use crate::cpu::{StepResult, CPU};
+
+/// Receives a callback after every instruction the CPU executes.
+///
+/// The core performs no I/O of its own; anything that wants to report on
+/// execution (tracers, profilers, test probes) attaches an observer with
+/// [`CPU::set_observer`].
+pub trait Observer {
+    fn on_step(&mut self, cpu: &CPU, result: &StepResult);
+}
+
+impl<F: FnMut(&CPU, &StepResult)> Observer for F {
+    fn on_step(&mut self, cpu: &CPU, result: &StepResult) {
+        self(cpu, result)
+    }
+}
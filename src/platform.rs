@@ -0,0 +1,52 @@
+use crate::cpu::{MEMORY_4K, MEMORY_64K};
+use crate::instruction::Instruction;
+use crate::quirks::Quirks;
+
+/// Which instruction set the CPU accepts. Extension opcodes executed on a
+/// platform that lacks them fault as illegal, except those encoded in the
+/// 0NNN range, which stay ignored SYS calls there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1: hi-res mode, scrolling, 16x16 sprites, large font and
+    /// RPL flags.
+    SuperChip,
+    /// XO-CHIP: everything in SUPER-CHIP plus 64 KB of memory, two
+    /// bitplanes, register range save/load and the audio pattern buffer.
+    XoChip,
+}
+
+impl Platform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chip8" => Some(Platform::Chip8),
+            "schip" => Some(Platform::SuperChip),
+            "xo-chip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+    /// The quirk preset ROMs for this platform usually expect.
+    pub fn default_quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::modern(),
+            Platform::SuperChip => Quirks::schip_modern(),
+            Platform::XoChip => Quirks::xo_chip(),
+        }
+    }
+    /// Bytes of addressable memory the platform provides.
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => MEMORY_4K,
+            Platform::XoChip => MEMORY_64K,
+        }
+    }
+    /// Whether `instruction` exists on this platform.
+    pub fn supports(self, instruction: &Instruction) -> bool {
+        match self {
+            Platform::Chip8 => !instruction.is_schip() && !instruction.is_xo_chip(),
+            Platform::SuperChip => !instruction.is_xo_chip(),
+            Platform::XoChip => true,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+/// Behaviour that differs between CHIP-8 interpreters for the same opcode.
+///
+/// Pick a preset matching the platform a ROM was written for, or start from
+/// one and flip individual fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VY into VX instead of shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// FX55/FX65 leave I pointing one past the last register transferred.
+    pub load_store_increments_i: bool,
+    /// BNNN jumps to XNN + VX instead of NNN + V0.
+    pub jump_uses_vx: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to 0.
+    pub logic_resets_vf: bool,
+    /// FX1E sets VF to 1 when I moves past 0xFFF and to 0 otherwise, as the
+    /// Amiga interpreter did.
+    pub index_overflow_sets_vf: bool,
+    /// Sprites that run off an edge wrap to the opposite side instead of
+    /// being clipped.
+    pub wrap_sprites: bool,
+    /// DXYN waits for the next 60 Hz timer tick before drawing, limiting
+    /// drawing to one sprite per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// Names accepted by [`Quirks::from_name`].
+    pub const PRESET_NAMES: [&'static str; 5] =
+        ["vip", "modern", "schip-legacy", "schip", "xo-chip"];
+
+    /// The original COSMAC VIP interpreter.
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            index_overflow_sets_vf: false,
+            wrap_sprites: false,
+            display_wait: true,
+        }
+    }
+    /// The behaviour most ROMs written since CHIP-48 expect.
+    pub fn modern() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            index_overflow_sets_vf: false,
+            wrap_sprites: false,
+            display_wait: false,
+        }
+    }
+    /// SUPER-CHIP 1.1 as it ran on the HP 48.
+    pub fn schip_legacy() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            index_overflow_sets_vf: false,
+            wrap_sprites: false,
+            display_wait: true,
+        }
+    }
+    /// SUPER-CHIP as implemented by modern interpreters such as Octo.
+    pub fn schip_modern() -> Self {
+        Quirks {
+            display_wait: false,
+            ..Self::schip_legacy()
+        }
+    }
+    /// XO-CHIP as specified by Octo.
+    pub fn xo_chip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            index_overflow_sets_vf: false,
+            wrap_sprites: true,
+            display_wait: false,
+        }
+    }
+    /// Looks up a preset by one of [`Quirks::PRESET_NAMES`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vip" => Some(Self::cosmac_vip()),
+            "modern" => Some(Self::modern()),
+            "schip-legacy" => Some(Self::schip_legacy()),
+            "schip" => Some(Self::schip_modern()),
+            "xo-chip" => Some(Self::xo_chip()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::modern()
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the random bytes CXNN masks with NN.
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+}
+
+/// Small xorshift64 generator. The same seed always yields the same
+/// sequence, so seeded runs are reproducible.
+#[derive(Debug, Clone)]
+pub struct XorShiftRng {
+    state: u64,
+}
+
+impl XorShiftRng {
+    /// The seed is scrambled with a SplitMix64 round so small seeds don't
+    /// start with a run of near-zero bytes.
+    pub fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // Xorshift gets stuck at zero.
+        let state = if z == 0 { 0x2545_F491_4F6C_DD1D } else { z };
+        XorShiftRng { state }
+    }
+    /// Seeds from the system clock, for interactive runs.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+}
+
+impl Default for XorShiftRng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl RandomSource for XorShiftRng {
+    fn next_byte(&mut self) -> u8 {
+        let mut s = self.state;
+        s ^= s << 13;
+        s ^= s >> 7;
+        s ^= s << 17;
+        self.state = s;
+        (s >> 56) as u8
+    }
+}
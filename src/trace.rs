@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::cpu::{StepResult, CPU};
+use crate::instruction::Instruction;
+use crate::observer::Observer;
+
+/// An [`Observer`] that logs one line per executed instruction:
+///
+/// ```text
+///        3  204: 2216  CALL  0x216             VF=00 SP=1
+///        4  216: 8014  ADD   V0, V1            VF=00 SP=1  V0=05->0f
+/// ```
+///
+/// Each line holds the cycle count (instructions executed since the tracer
+/// was attached, starting at 1), the address and opcode, the disassembled
+/// instruction, VF, the stack pointer and every register the instruction
+/// changed. Attach it with [`CPU::set_observer`]; it then traces both
+/// [`CPU::step`] and [`CPU::run`].
+///
+/// Write errors are ignored so that a closed pipe never stops the program
+/// being traced.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    cycle: u64,
+    addresses: Option<RangeInclusive<usize>>,
+    cycles: Option<RangeInclusive<u64>>,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Tracer {
+            out,
+            cycle: 0,
+            addresses: None,
+            cycles: None,
+        }
+    }
+    pub fn stdout() -> Self {
+        Self::new(Box::new(io::stdout()))
+    }
+    /// Traces to a newly created file, replacing any existing one.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(Box::new(BufWriter::new(File::create(path)?))))
+    }
+    /// Only logs instructions fetched from addresses in `range`.
+    pub fn with_addresses(mut self, range: RangeInclusive<usize>) -> Self {
+        self.addresses = Some(range);
+        self
+    }
+    /// Only logs instructions whose cycle count falls in `range`.
+    pub fn with_cycles(mut self, range: RangeInclusive<u64>) -> Self {
+        self.cycles = Some(range);
+        self
+    }
+    fn line(cycle: u64, cpu: &CPU, result: &StepResult) -> String {
+        let instruction = match result.instruction {
+            // The address word has been consumed into I by now.
+            Instruction::LongLoadIndex => format!("LD    I, LONG {:#06x}", cpu.index()),
+            instruction if instruction.operands().is_empty() => instruction.mnemonic().to_string(),
+            instruction => format!("{:<5} {}", instruction.mnemonic(), instruction.operands()),
+        };
+        let changes: Vec<String> = result
+            .registers_before
+            .iter()
+            .zip(cpu.registers())
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(i, (before, after))| format!("V{:X}={:02x}->{:02x}", i, before, after))
+            .collect();
+        let line = format!(
+            "{:>8}  {:03x}: {:04x}  {:<22}  VF={:02x} SP={}  {}",
+            cycle,
+            result.addr,
+            result.opcode,
+            instruction,
+            cpu.registers()[0xF],
+            cpu.stack_pointer(),
+            changes.join(" ")
+        );
+        line.trim_end().to_string()
+    }
+}
+
+impl Observer for Tracer {
+    fn on_step(&mut self, cpu: &CPU, result: &StepResult) {
+        self.cycle += 1;
+        if let Some(addresses) = &self.addresses {
+            if !addresses.contains(&result.addr) {
+                return;
+            }
+        }
+        if let Some(cycles) = &self.cycles {
+            if !cycles.contains(&self.cycle) {
+                return;
+            }
+        }
+        let _ = writeln!(self.out, "{}", Self::line(self.cycle, cpu, result));
+    }
+}